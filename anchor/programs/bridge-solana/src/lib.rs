@@ -1,6 +1,21 @@
 use anchor_lang::prelude::*;
-use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{Mint, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id, AssociatedToken, Create,
+};
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, SyncNative, TokenAccount, TokenInterface,
+    TransferChecked,
+};
 
 declare_id!("Fc8Kj9zkE7fLuLnpxZyArs3oJj3EKuaLj9XNGQ2GQbwY");
 
@@ -19,12 +34,20 @@ pub struct Bytes32 {
 // the bridge owner is trusted with relaying information from other chains
 // the program supports fees & send limits
 
+const MAX_FEE: u16 = 10000;
+// seconds after the unlock during which a requested withdraw override can be used
+const WITHDRAW_OVERRIDE_WINDOW: i64 = 86400;
+// shortest timelock of the withdraw override in seconds, also the default of new instances
+// the localnet feature shortens it so the tests can wait for it
+#[cfg(not(feature = "localnet"))]
+const MIN_WITHDRAW_OVERRIDE_DELAY: i64 = 86400;
+#[cfg(feature = "localnet")]
+const MIN_WITHDRAW_OVERRIDE_DELAY: i64 = 2;
+
 #[program]
 pub mod bridge_solana {
     use super::*;
 
-    const MAX_FEE: u16 = 10000;
-
     // initialize a bridge instance
     // bridge can be paused
     // _version allows having multiple bridge instances for one token, owner and chain
@@ -35,6 +58,7 @@ pub mod bridge_solana {
         fee_send: u16,
         fee_fulfill: u16,
         limit_send: u64,
+        limit_fulfill: u64,
         paused: bool,
         _version: u64,
         _current_chain: Bytes32,
@@ -45,19 +69,23 @@ pub mod bridge_solana {
         ctx.accounts.bridge_params.fee_send = fee_send;
         ctx.accounts.bridge_params.fee_fulfill = fee_fulfill;
         ctx.accounts.bridge_params.limit_send = limit_send;
+        ctx.accounts.bridge_params.limit_fulfill = limit_fulfill;
         ctx.accounts.bridge_params.fee_recipient = ctx.accounts.fee_account.key();
         ctx.accounts.bridge_params.paused = paused;
+        ctx.accounts.bridge_params.withdraw_override_delay = MIN_WITHDRAW_OVERRIDE_DELAY;
         Ok(())
     }
 
     // change params of a bridge instance
     // limit_send is the maximum amount of token allowed to be bridged in a single tx
+    // limit_fulfill is the maximum amount of token allowed to be released in a single fulfill
     pub fn set_params(
         ctx: Context<SetParams>,
         _token_mint: Pubkey,
         fee_send: u16,
         fee_fulfill: u16,
         limit_send: u64,
+        limit_fulfill: u64,
         paused: bool,
         _version: u64,
         _current_chain: Bytes32,
@@ -68,112 +96,504 @@ pub mod bridge_solana {
         ctx.accounts.bridge_params.fee_send = fee_send;
         ctx.accounts.bridge_params.fee_fulfill = fee_fulfill;
         ctx.accounts.bridge_params.limit_send = limit_send;
+        ctx.accounts.bridge_params.limit_fulfill = limit_fulfill;
         ctx.accounts.bridge_params.fee_recipient = ctx.accounts.fee_account.key();
-        ctx.accounts.bridge_params.paused = paused;
+        set_paused(&mut ctx.accounts.bridge_params, paused);
+        Ok(())
+    }
+
+    // migrate the params of an instance created before the params were extended
+    // the account is resized, paid by the owner, and the new params get safe defaults:
+    // fulfills are not limited and all tokens held by the bridge count as liabilities,
+    // so they can only be withdrawn with the withdraw override, which starts at the minimum delay
+    pub fn migrate_params(
+        ctx: Context<MigrateParams>,
+        _token_mint: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let bridge_params = ctx.accounts.bridge_params.to_account_info();
+        // limit_fulfill was added in the middle of the params, so the legacy params have to be
+        // read before resizing
+        let legacy_params = {
+            let data = bridge_params.try_borrow_data()?;
+            require_eq!(
+                data.len(),
+                8 + LegacyBridgeParams::MAX_SIZE,
+                BridgeError::AlreadyMigrated
+            );
+            require!(
+                data[..8] == BridgeParams::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            LegacyBridgeParams::deserialize(&mut &data[8..])?
+        };
+
+        resize_account(
+            bridge_params.clone(),
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            8 + BridgeParams::MAX_SIZE,
+        )?;
+
+        let params = BridgeParams {
+            fee_send: legacy_params.fee_send,
+            fee_fulfill: legacy_params.fee_fulfill,
+            limit_send: legacy_params.limit_send,
+            limit_fulfill: u64::MAX,
+            fee_recipient: legacy_params.fee_recipient,
+            paused: legacy_params.paused,
+            liabilities: ctx.accounts.bridge_token_account.amount,
+            withdraw_override_delay: MIN_WITHDRAW_OVERRIDE_DELAY,
+            ..Default::default()
+        };
+        let mut data = bridge_params.try_borrow_mut_data()?;
+        params.try_serialize(&mut &mut data[..])
+    }
+
+    // migrate the data of a chain added before the chain data was extended
+    // the account is resized, paid by the owner, and the new fields are zero, which disables
+    // the native drop and the mint cap and leaves the route unset
+    pub fn migrate_chain_data(
+        ctx: Context<MigrateChainData>,
+        _token_mint: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+        _chain: Bytes32,
+    ) -> Result<()> {
+        let chain_data = ctx.accounts.chain_data.to_account_info();
+        {
+            let data = chain_data.try_borrow_data()?;
+            require_gt!(
+                8 + ChainData::MAX_SIZE,
+                data.len(),
+                BridgeError::AlreadyMigrated
+            );
+            require!(
+                data.len() >= 8 && data[..8] == ChainData::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+        }
+
+        resize_account(
+            chain_data,
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            8 + ChainData::MAX_SIZE,
+        )
+    }
+
+    // create a registry for the instances of the owner on the current chain
+    // instances of different mints added to the registry are paused while the registry is,
+    // in addition to their own pause state, while the owner is their admin and relayer anyway
+    // the registry only shares the pause, each instance keeps its own params, vault and chain data
+    pub fn initialize_registry(
+        _ctx: Context<InitializeRegistry>,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    // add the instance of the token mint to the registry
+    pub fn register_mint(
+        ctx: Context<RegisterMint>,
+        token_mint: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        require!(
+            !registry.mints.contains(&token_mint),
+            BridgeError::AlreadyRegistered
+        );
+        require_gt!(
+            Registry::MAX_MINTS,
+            registry.mints.len(),
+            BridgeError::RegistryFull
+        );
+
+        registry.mints.push(token_mint);
+        Ok(())
+    }
+
+    // remove the instance of the token mint from the registry
+    // the instance is no longer paused by the registry afterwards
+    pub fn unregister_mint(
+        ctx: Context<UnregisterMint>,
+        token_mint: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let index = registry
+            .mints
+            .iter()
+            .position(|mint| *mint == token_mint)
+            .ok_or(BridgeError::NotRegistered)?;
+        registry.mints.remove(index);
+        Ok(())
+    }
+
+    // pause or unpause all instances of the registry
+    // the registry is checked on each send, fulfill, release and claim of its instances,
+    // the pause state of the instances themselves is left as is
+    pub fn set_registry_paused(
+        ctx: Context<SetRegistryPaused>,
+        paused: bool,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.registry.paused = paused;
+        Ok(())
+    }
+
+    // set the circuit breaker params
+    // the bridge is paused automatically once more than outflow_limit / MAX_FEE of the
+    // bridge balance is released within outflow_window seconds, 0 disables the circuit breaker
+    pub fn set_circuit_breaker(
+        ctx: Context<SetCircuitBreaker>,
+        _token_mint: Pubkey,
+        outflow_limit: u16,
+        outflow_window: i64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gte!(MAX_FEE, outflow_limit, BridgeError::OutflowLimitTooHigh);
+        // the window is only used while the circuit breaker is enabled
+        if outflow_limit > 0 {
+            require_gt!(outflow_window, 0, BridgeError::PeriodZero);
+        }
+
+        ctx.accounts.bridge_params.outflow_limit = outflow_limit;
+        ctx.accounts.bridge_params.outflow_window = outflow_window;
         Ok(())
     }
 
     // allow/ban specific chains and set the exchange rate for the chain
     // exchange rate is a multiplier that fixes the difference between decimals on different
     // chains
+    // nonce_per_user is set for chains that count nonces per sender, like Solana itself,
+    // instead of one global nonce. it can only be chosen when the chain data is created,
+    // as changing it would move fulfilled nonces to other bitmap pages
     pub fn set_chain_data(
         ctx: Context<SetChainData>,
         _token_mint: Pubkey,
         enabled: bool,
         exchange_rate_from: u64,
+        nonce_per_user: bool,
         _version: u64,
         _current_chain: Bytes32,
         _chain: Bytes32,
     ) -> Result<()> {
         require_gt!(exchange_rate_from, 0, BridgeError::ExchangeRateZero);
+        // the exchange rate is never 0 once the chain data exists
+        if ctx.accounts.chain_data.exchange_rate_from == 0 {
+            ctx.accounts.chain_data.nonce_per_user = nonce_per_user;
+        }
+        require!(
+            ctx.accounts.chain_data.nonce_per_user == nonce_per_user,
+            BridgeError::NonceModeChanged
+        );
         ctx.accounts.chain_data.enabled = enabled;
         ctx.accounts.chain_data.exchange_rate_from = exchange_rate_from;
         Ok(())
     }
 
-    // fulfill a bridge tx from another chain
-    pub fn fulfill(
-        ctx: Context<Fulfill>,
-        _nonce: u64,
-        amount: u64,
-        version: u64,
-        current_chain: Bytes32,
-        _from_chain: Bytes32,
+    // set the address of the token and the bridge contract on the chain
+    // sends to the chain record them, and fulfills from the chain have to come from them
+    pub fn set_remote_route(
+        ctx: Context<SetRemoteRoute>,
+        _token_mint: Pubkey,
+        remote_token: Bytes32,
+        remote_bridge: Bytes32,
+        _version: u64,
+        _current_chain: Bytes32,
+        _chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.chain_data.remote_token = remote_token;
+        ctx.accounts.chain_data.remote_bridge = remote_bridge;
+        Ok(())
+    }
+
+    // set the amount of lamports sent to recipients of fulfills from the chain, 0 disables it
+    // native_drop_fee is the extra fee in token taken for the native drop
+    // the native drop has to cover the rent of an empty account, as the recipient might not exist yet
+    pub fn set_native_drop(
+        ctx: Context<SetNativeDrop>,
+        _token_mint: Pubkey,
+        native_drop: u64,
+        native_drop_fee: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+        _chain: Bytes32,
     ) -> Result<()> {
         require!(
-            !ctx.accounts.bridge_params.paused,
-            BridgeError::BridgePaused
-        );
-        require!(
-            ctx.accounts.from_chain_data.enabled,
-            BridgeError::ChainDisabled
+            native_drop == 0 || native_drop >= Rent::get()?.minimum_balance(0),
+            BridgeError::NativeDropTooLow
         );
 
-        // the conversion is needed to account for decimal differences between chains
-        let amount_converted = amount * ctx.accounts.from_chain_data.exchange_rate_from;
-        let fee_fulfill = ctx.accounts.bridge_params.fee_fulfill;
-        // can not overflow as fee_fulfill is < MAX_FEE
-        let fee =
-            (u128::from(amount_converted) * u128::from(fee_fulfill) / u128::from(MAX_FEE)) as u64;
-        let amount_taxed = amount_converted - fee;
-
-        // transfer
+        ctx.accounts.chain_data.native_drop = native_drop;
+        ctx.accounts.chain_data.native_drop_fee = native_drop_fee;
+        Ok(())
+    }
 
-        require_gt!(amount_taxed, 0, BridgeError::AmountTooLow);
+    // transfer lamports to the sol vault used for native drops. can only be called by the owner
+    pub fn fund_sol_vault(
+        ctx: Context<ManageSolVault>,
+        _token_mint: Pubkey,
+        lamports: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.owner.to_account_info(),
+                to: ctx.accounts.sol_vault.to_account_info(),
+            },
+        );
+        system_program::transfer(cpi_ctx, lamports)
+    }
 
+    // withdraw lamports from the sol vault. can only be called by the owner
+    pub fn withdraw_sol_vault(
+        ctx: Context<ManageSolVault>,
+        token_mint: Pubkey,
+        lamports: u64,
+        version: u64,
+        current_chain: Bytes32,
+    ) -> Result<()> {
         let version = version.to_be_bytes();
         let owner_key = ctx.accounts.owner.key();
-        let token_mint_key = ctx.accounts.token_mint.key();
-        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+        let bump: u8 = *ctx.bumps.get("sol_vault").unwrap();
 
         let seeds = &[
             version.as_ref(),
-            b"wallet".as_ref(),
+            b"sol_vault".as_ref(),
             owner_key.as_ref(),
-            token_mint_key.as_ref(),
+            token_mint.as_ref(),
             current_chain.byte.as_ref(),
             &[bump],
         ];
         let signer = &[&seeds[..]];
 
-        let transfer_instruction = Transfer {
-            from: ctx.accounts.bridge_token_account.to_account_info(),
-            to: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.bridge_token_account.to_account_info(),
-        };
-
         let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            transfer_instruction,
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.sol_vault.to_account_info(),
+                to: ctx.accounts.owner.to_account_info(),
+            },
             signer,
         );
+        system_program::transfer(cpi_ctx, lamports)
+    }
 
-        anchor_spl::token::transfer(cpi_ctx, amount_taxed)?;
+    // set the per-user send limit
+    // limit_send_user is the maximum amount of token a single user is allowed to send
+    // within one period of limit_send_user_period seconds, 0 disables the limit
+    pub fn set_user_send_limit(
+        ctx: Context<SetUserSendLimit>,
+        _token_mint: Pubkey,
+        limit_send_user: u64,
+        limit_send_user_period: i64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gt!(limit_send_user_period, 0, BridgeError::PeriodZero);
 
-        if fee > 0 {
-            let transfer_instruction = Transfer {
-                from: ctx.accounts.bridge_token_account.to_account_info(),
-                to: ctx.accounts.fee_account.to_account_info(),
-                authority: ctx.accounts.bridge_token_account.to_account_info(),
-            };
+        ctx.accounts.bridge_params.limit_send_user = limit_send_user;
+        ctx.accounts.bridge_params.limit_send_user_period = limit_send_user_period;
+        Ok(())
+    }
 
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                transfer_instruction,
-                signer,
-            );
+    // exempt a user from the per-user send limit or revoke the exemption
+    pub fn set_user_send_limit_exempt(
+        ctx: Context<SetUserSendLimitExempt>,
+        _token_mint: Pubkey,
+        _user: Pubkey,
+        exempt: bool,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.send_limit.exempt = exempt;
+        Ok(())
+    }
+
+    // set the withdraw limit
+    // limit_withdraw is the maximum amount of token the owner is allowed to withdraw
+    // within one period of limit_withdraw_period seconds, 0 disables the limit
+    pub fn set_withdraw_limit(
+        ctx: Context<SetWithdrawLimit>,
+        _token_mint: Pubkey,
+        limit_withdraw: u64,
+        limit_withdraw_period: i64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gt!(limit_withdraw_period, 0, BridgeError::PeriodZero);
+
+        ctx.accounts.bridge_params.limit_withdraw = limit_withdraw;
+        ctx.accounts.bridge_params.limit_withdraw_period = limit_withdraw_period;
+        Ok(())
+    }
+
+    // start the timelock for withdrawing below the liabilities of the bridge
+    // after the withdraw override delay, the owner can withdraw once with override_liabilities
+    // set, within WITHDRAW_OVERRIDE_WINDOW seconds
+    pub fn request_withdraw_override(
+        ctx: Context<RequestWithdrawOverride>,
+        _token_mint: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let bridge_params = &mut ctx.accounts.bridge_params;
+        require_gt!(
+            bridge_params.withdraw_override_delay,
+            0,
+            BridgeError::PeriodZero
+        );
 
-            anchor_spl::token::transfer(cpi_ctx, fee)?;
+        let unlock_time = Clock::get()?.unix_timestamp + bridge_params.withdraw_override_delay;
+        bridge_params.withdraw_override_unlock = unlock_time;
+        emit!(WithdrawOverrideRequested {
+            liabilities: bridge_params.liabilities,
+            unlock_time,
+        });
+        Ok(())
+    }
+
+    // set the timelock of the withdraw override in seconds, at least MIN_WITHDRAW_OVERRIDE_DELAY
+    // raising it takes effect immediately, while lowering it uses up an unlocked override,
+    // so it can not be lowered faster than the current delay
+    pub fn set_withdraw_override_delay(
+        ctx: Context<SetWithdrawOverrideDelay>,
+        _token_mint: Pubkey,
+        withdraw_override_delay: i64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gte!(
+            withdraw_override_delay,
+            MIN_WITHDRAW_OVERRIDE_DELAY,
+            BridgeError::WithdrawOverrideDelayTooShort
+        );
+
+        let bridge_params = &mut ctx.accounts.bridge_params;
+        if withdraw_override_delay < bridge_params.withdraw_override_delay {
+            use_withdraw_override(bridge_params)?;
         }
+        bridge_params.withdraw_override_delay = withdraw_override_delay;
         Ok(())
     }
 
-    // withdraw tokens from the bridge. can only be called by the owner
-    pub fn withdraw(ctx: Context<Withdraw>, version: u64, current_chain: Bytes32) -> Result<()> {
-        // transfer
-        let amount = ctx.accounts.bridge_token_account.amount;
+    // allow or disallow a token account as a destination of withdraws
+    pub fn set_withdraw_destination(
+        ctx: Context<SetWithdrawDestination>,
+        _token_mint: Pubkey,
+        _destination: Pubkey,
+        allowed: bool,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.withdraw_destination.allowed = allowed;
+        Ok(())
+    }
 
-        require_gt!(amount, 0, BridgeError::WithdrawZero);
+    // fulfill a bridge tx from another chain
+    // the tokens are sent to the associated token account of `to`, which does not have to sign
+    // the tx, and the rent for the new accounts is paid by `payer`, e.g. the backend
+    // if the source chain has a native drop set, `to` also receives that many lamports
+    // from_user is the sender on the source chain, the nonce is unique per source chain and sender
+    // the fulfill can not be executed after deadline, a unix timestamp
+    // remote_token and remote_bridge are the token and bridge contract the tx was sent from
+    pub fn fulfill<'info>(
+        ctx: Context<'_, '_, '_, 'info, Fulfill<'info>>,
+        nonce: u64,
+        amount: u64,
+        to: Pubkey,
+        version: u64,
+        current_chain: Bytes32,
+        from_chain: Bytes32,
+        from_user: Bytes32,
+        deadline: i64,
+        source_tx_hash: Bytes32,
+        source_block: u64,
+        remote_token: Bytes32,
+        remote_bridge: Bytes32,
+    ) -> Result<()> {
+        let (mut amount_taxed, mut fee) = fulfill_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.from_chain_data,
+            amount,
+            deadline,
+            remote_token,
+            remote_bridge,
+        )?;
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+
+        mark_fulfilled(
+            &ctx.accounts.fulfilled_bitmap,
+            ctx.accounts.fulfill_receipt.as_deref_mut(),
+            TxFulfilled {
+                from_chain,
+                from_user,
+                nonce,
+                amount,
+                recipient: to,
+                source_tx_hash,
+                source_block,
+                slot: Clock::get()?.slot,
+            },
+        )?;
+
+        record_mint(
+            &ctx.accounts.bridge_params,
+            &mut ctx.accounts.from_chain_data,
+            amount_taxed + fee,
+        )?;
+
+        // transfers above the threshold have to go through fulfill_delayed
+        let release_threshold = ctx.accounts.bridge_params.release_threshold;
+        require!(
+            release_threshold == 0 || amount_taxed + fee <= release_threshold,
+            BridgeError::ReleaseDelayRequired
+        );
+
+        // the native drop is paid for with an extra fee
+        // it is skipped if the sol vault can not cover it, the vault has to stay rent-exempt
+        // unless the drop empties it
+        let native_drop = ctx.accounts.from_chain_data.native_drop;
+        let vault_lamports = ctx.accounts.sol_vault.lamports();
+        let drop_native = native_drop > 0
+            && (vault_lamports == native_drop
+                || vault_lamports >= native_drop + Rent::get()?.minimum_balance(0));
+        if drop_native {
+            let native_drop_fee = ctx.accounts.from_chain_data.native_drop_fee;
+            require_gt!(amount_taxed, native_drop_fee, BridgeError::AmountTooLow);
+            amount_taxed -= native_drop_fee;
+            fee += native_drop_fee;
+        }
+
+        prepare_user_token_account(
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+
+        record_outflow(
+            &mut ctx.accounts.bridge_params,
+            ctx.accounts.bridge_token_account.amount,
+            ctx.accounts.token_mint.supply,
+            amount_taxed + fee,
+        )?;
+        settle_liabilities(&mut ctx.accounts.bridge_params, amount_taxed + fee);
+
+        // transfer
 
         let version = version.to_be_bytes();
         let owner_key = ctx.accounts.owner.key();
@@ -190,176 +610,3240 @@ pub mod bridge_solana {
         ];
         let signer = &[&seeds[..]];
 
-        let transfer_instruction = Transfer {
-            from: ctx.accounts.bridge_token_account.to_account_info(),
-            to: ctx.accounts.withdraw_token_account.to_account_info(),
-            authority: ctx.accounts.bridge_token_account.to_account_info(),
-        };
+        if ctx.accounts.bridge_params.burn_and_mint {
+            mint_to_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                amount_taxed + fee,
+            )?;
+        }
 
-        let cpi_ctx = CpiContext::new_with_signer(
+        transfer_from_bridge(
             ctx.accounts.token_program.to_account_info(),
-            transfer_instruction,
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.token_mint,
             signer,
-        );
+            ctx.remaining_accounts,
+            amount_taxed,
+        )?;
+
+        if fee > 0 {
+            transfer_from_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                ctx.accounts.fee_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                ctx.remaining_accounts,
+                fee,
+            )?;
+        }
+
+        if drop_native {
+            let bump: u8 = *ctx.bumps.get("sol_vault").unwrap();
+            let seeds = &[
+                version.as_ref(),
+                b"sol_vault".as_ref(),
+                owner_key.as_ref(),
+                token_mint_key.as_ref(),
+                current_chain.byte.as_ref(),
+                &[bump],
+            ];
+            let signer = &[&seeds[..]];
 
-        anchor_spl::token::transfer(cpi_ctx, amount)?;
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.sol_vault.to_account_info(),
+                    to: ctx.accounts.user.to_account_info(),
+                },
+                signer,
+            );
+            system_program::transfer(cpi_ctx, native_drop)?;
+        }
         Ok(())
     }
 
-    // send a bridge tx to another chain
-    pub fn send(
-        ctx: Context<Send>,
-        _owner: Pubkey,
-        _token_mint: Pubkey,
+    // fulfill a bridge tx of native SOL from another chain
+    // the instance has to use the native mint. the wrapped SOL is moved to a temporary token
+    // account, which is closed to the payer, who passes the lamports on to the recipient,
+    // so the recipient receives native SOL
+    pub fn fulfill_native<'info>(
+        ctx: Context<'_, '_, '_, 'info, FulfillNative<'info>>,
+        nonce: u64,
         amount: u64,
-        to: Bytes32,
-        _version: u64,
-        to_chain: Bytes32,
-        _current_chain: Bytes32,
+        to: Pubkey,
+        version: u64,
+        current_chain: Bytes32,
+        from_chain: Bytes32,
+        from_user: Bytes32,
+        deadline: i64,
+        source_tx_hash: Bytes32,
+        source_block: u64,
+        remote_token: Bytes32,
+        remote_bridge: Bytes32,
     ) -> Result<()> {
+        let (amount_taxed, fee) = fulfill_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.from_chain_data,
+            amount,
+            deadline,
+            remote_token,
+            remote_bridge,
+        )?;
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+
+        // a new recipient account has to receive at least the rent exemption,
+        // otherwise the final transfer of the lamports fails
         require!(
-            !ctx.accounts.bridge_params.paused,
-            BridgeError::BridgePaused
+            ctx.accounts.user.lamports() > 0 || amount_taxed >= Rent::get()?.minimum_balance(0),
+            BridgeError::AmountBelowRent
         );
+
+        mark_fulfilled(
+            &ctx.accounts.fulfilled_bitmap,
+            ctx.accounts.fulfill_receipt.as_deref_mut(),
+            TxFulfilled {
+                from_chain,
+                from_user,
+                nonce,
+                amount,
+                recipient: to,
+                source_tx_hash,
+                source_block,
+                slot: Clock::get()?.slot,
+            },
+        )?;
+
+        // transfers above the threshold have to go through fulfill_delayed
+        let release_threshold = ctx.accounts.bridge_params.release_threshold;
         require!(
-            ctx.accounts.to_chain_data.enabled,
-            BridgeError::ChainDisabled
-        );
-        require_gte!(
-            ctx.accounts.bridge_params.limit_send,
-            amount,
-            BridgeError::SendLimitExceeded
+            release_threshold == 0 || amount_taxed + fee <= release_threshold,
+            BridgeError::ReleaseDelayRequired
         );
 
-        // prevent a small amount of token from being lost
-        require_eq!(
-            amount % ctx.accounts.to_chain_data.exchange_rate_from,
-            0,
-            BridgeError::AmountUneven
-        );
+        record_outflow(
+            &mut ctx.accounts.bridge_params,
+            ctx.accounts.bridge_token_account.amount,
+            ctx.accounts.token_mint.supply,
+            amount_taxed + fee,
+        )?;
+        settle_liabilities(&mut ctx.accounts.bridge_params, amount_taxed + fee);
 
-        // make sure at least some fee will be taken from each bridge tx
-        require_gte!(
-            amount / ctx.accounts.to_chain_data.exchange_rate_from,
-            u64::from(MAX_FEE),
-            BridgeError::AmountTooLow
-        );
+        // transfer
 
-        // the conversion is needed to account for decimal differences between chains
-        let amount_converted = amount / ctx.accounts.to_chain_data.exchange_rate_from;
-        let fee_send = ctx.accounts.bridge_params.fee_send;
-        // can not overflow as fee_send is < MAX_FEE
-        let fee =
-            (u128::from(amount_converted) * u128::from(fee_send) / u128::from(MAX_FEE)) as u64;
-        let amount_taxed = amount_converted - fee;
+        let version = version.to_be_bytes();
+        let owner_key = ctx.accounts.owner.key();
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
 
-        // transfer token to the bridge
+        let seeds = &[
+            version.as_ref(),
+            b"wallet".as_ref(),
+            owner_key.as_ref(),
+            token_mint_key.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
 
-        let transfer_instruction = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.bridge_token_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(), // "from" authority
-        };
-        let cpi_ctx = CpiContext::new(
+        transfer_from_bridge(
             ctx.accounts.token_program.to_account_info(),
-            transfer_instruction,
-        );
-
-        anchor_spl::token::transfer(
-            cpi_ctx,
-            amount_taxed * ctx.accounts.to_chain_data.exchange_rate_from,
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.unwrap_account.to_account_info(),
+            &ctx.accounts.token_mint,
+            signer,
+            ctx.remaining_accounts,
+            amount_taxed,
         )?;
 
-        // transfer fee to the fee account
         if fee > 0 {
-            let transfer_instruction = Transfer {
-                from: ctx.accounts.user_token_account.to_account_info(),
-                to: ctx.accounts.fee_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(), // "from" authority
-            };
-            let cpi_ctx = CpiContext::new(
+            transfer_from_bridge(
                 ctx.accounts.token_program.to_account_info(),
-                transfer_instruction,
-            );
-
-            anchor_spl::token::transfer(
-                cpi_ctx,
-                fee * ctx.accounts.to_chain_data.exchange_rate_from,
+                ctx.accounts.bridge_token_account.to_account_info(),
+                ctx.accounts.fee_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                ctx.remaining_accounts,
+                fee,
             )?;
         }
 
-        // write send tx to the account
+        // unwrap
+
+        let bump: u8 = *ctx.bumps.get("unwrap_account").unwrap();
+        let seeds = &[
+            version.as_ref(),
+            b"unwrap".as_ref(),
+            owner_key.as_ref(),
+            token_mint_key.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // the payer receives the rent back along with the unwrapped lamports
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.unwrap_account.to_account_info(),
+                destination: ctx.accounts.payer.to_account_info(),
+                authority: ctx.accounts.unwrap_account.to_account_info(),
+            },
+            signer,
+        );
+        token_interface::close_account(cpi_ctx)?;
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: ctx.accounts.user.to_account_info(),
+            },
+        );
+        system_program::transfer(cpi_ctx, amount_taxed)
+    }
+
+    // fulfill a bridge tx from another chain without releasing the tokens immediately
+    // the tokens can be released by anyone with `release` after the release delay,
+    // until then the guardian can cancel the release with `veto`
+    pub fn fulfill_delayed(
+        ctx: Context<FulfillDelayed>,
+        nonce: u64,
+        amount: u64,
+        to: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+        from_chain: Bytes32,
+        from_user: Bytes32,
+        deadline: i64,
+        source_tx_hash: Bytes32,
+        source_block: u64,
+        remote_token: Bytes32,
+        remote_bridge: Bytes32,
+    ) -> Result<()> {
+        let (amount_taxed, fee) = fulfill_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.from_chain_data,
+            amount,
+            deadline,
+            remote_token,
+            remote_bridge,
+        )?;
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+
+        mark_fulfilled(
+            &ctx.accounts.fulfilled_bitmap,
+            ctx.accounts.fulfill_receipt.as_deref_mut(),
+            TxFulfilled {
+                from_chain,
+                from_user,
+                nonce,
+                amount,
+                recipient: to,
+                source_tx_hash,
+                source_block,
+                slot: Clock::get()?.slot,
+            },
+        )?;
+
+        // vetoed releases stay counted against the mint cap
+        record_mint(
+            &ctx.accounts.bridge_params,
+            &mut ctx.accounts.from_chain_data,
+            amount_taxed + fee,
+        )?;
+
+        prepare_user_token_account(
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+
+        let pending_release = &mut ctx.accounts.pending_release;
+        pending_release.user_token_account = ctx.accounts.user_token_account.key();
+        pending_release.amount = amount_taxed;
+        pending_release.fee = fee;
+        pending_release.unlock_time =
+            Clock::get()?.unix_timestamp + ctx.accounts.bridge_params.release_delay;
+        pending_release.payer = ctx.accounts.payer.key();
+
+        reserve_payout(&mut ctx.accounts.bridge_params, amount_taxed + fee);
+        Ok(())
+    }
+
+    // release the tokens of a delayed fulfill after the release delay has passed
+    // can be called by anyone
+    pub fn release<'info>(
+        ctx: Context<'_, '_, '_, 'info, Release<'info>>,
+        owner: Pubkey,
+        token_mint: Pubkey,
+        _nonce: u64,
+        version: u64,
+        current_chain: Bytes32,
+        _from_chain: Bytes32,
+        _from_user: Bytes32,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.bridge_params.paused,
+            BridgeError::BridgePaused
+        );
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+        require_gte!(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.pending_release.unlock_time,
+            BridgeError::ReleaseLocked
+        );
+
+        record_outflow(
+            &mut ctx.accounts.bridge_params,
+            ctx.accounts.bridge_token_account.amount,
+            ctx.accounts.token_mint.supply,
+            ctx.accounts.pending_release.amount + ctx.accounts.pending_release.fee,
+        )?;
+        settle_payout(
+            &mut ctx.accounts.bridge_params,
+            ctx.accounts.pending_release.amount + ctx.accounts.pending_release.fee,
+        );
+
+        // transfer
+
+        let version = version.to_be_bytes();
+        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+
+        let seeds = &[
+            version.as_ref(),
+            b"wallet".as_ref(),
+            owner.as_ref(),
+            token_mint.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        if ctx.accounts.bridge_params.burn_and_mint {
+            mint_to_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                ctx.accounts.pending_release.amount + ctx.accounts.pending_release.fee,
+            )?;
+        }
+
+        transfer_from_bridge(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.token_mint,
+            signer,
+            ctx.remaining_accounts,
+            ctx.accounts.pending_release.amount,
+        )?;
+
+        let fee = ctx.accounts.pending_release.fee;
+        if fee > 0 {
+            transfer_from_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                ctx.accounts.fee_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                ctx.remaining_accounts,
+                fee,
+            )?;
+        }
+        Ok(())
+    }
+
+    // cancel a delayed fulfill. can only be called by the guardian
+    // the tokens stay in the bridge and the nonce stays used
+    // the tokens count as liabilities again, like before the fulfill
+    pub fn veto(
+        ctx: Context<Veto>,
+        _owner: Pubkey,
+        _token_mint: Pubkey,
+        _nonce: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+        _from_chain: Bytes32,
+        _from_user: Bytes32,
+    ) -> Result<()> {
+        let amount = ctx.accounts.pending_release.amount + ctx.accounts.pending_release.fee;
+        let bridge_params = &mut ctx.accounts.bridge_params;
+        if !bridge_params.burn_and_mint {
+            settle_payout(bridge_params, amount);
+            bridge_params.liabilities += amount;
+        }
+        Ok(())
+    }
+
+    // fulfill a bridge tx from another chain into a claimable account
+    // instead of sending the tokens, the tx is stored until the recipient claims
+    // it with `claim`, so it can be fulfilled without knowing the recipient's token account
+    pub fn fulfill_claimable(
+        ctx: Context<FulfillClaimable>,
+        nonce: u64,
+        amount: u64,
+        to: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+        from_chain: Bytes32,
+        from_user: Bytes32,
+        deadline: i64,
+        source_tx_hash: Bytes32,
+        source_block: u64,
+        remote_token: Bytes32,
+        remote_bridge: Bytes32,
+    ) -> Result<()> {
+        let (amount_taxed, fee) = fulfill_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.from_chain_data,
+            amount,
+            deadline,
+            remote_token,
+            remote_bridge,
+        )?;
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+
+        mark_fulfilled(
+            &ctx.accounts.fulfilled_bitmap,
+            ctx.accounts.fulfill_receipt.as_deref_mut(),
+            TxFulfilled {
+                from_chain,
+                from_user,
+                nonce,
+                amount,
+                recipient: to,
+                source_tx_hash,
+                source_block,
+                slot: Clock::get()?.slot,
+            },
+        )?;
+
+        record_mint(
+            &ctx.accounts.bridge_params,
+            &mut ctx.accounts.from_chain_data,
+            amount_taxed + fee,
+        )?;
+
+        // transfers above the threshold have to go through fulfill_delayed
+        let release_threshold = ctx.accounts.bridge_params.release_threshold;
+        require!(
+            release_threshold == 0 || amount_taxed + fee <= release_threshold,
+            BridgeError::ReleaseDelayRequired
+        );
+
+        let claimable_tx = &mut ctx.accounts.claimable_tx;
+        claimable_tx.recipient = to;
+        claimable_tx.amount = amount_taxed;
+        claimable_tx.fee = fee;
+        claimable_tx.payer = ctx.accounts.payer.key();
+
+        reserve_payout(&mut ctx.accounts.bridge_params, amount_taxed + fee);
+        Ok(())
+    }
+
+    // claim the tokens of a claimable fulfill. can only be called by the recipient,
+    // who also pays the rent for their token account if it has to be created
+    pub fn claim<'info>(
+        ctx: Context<'_, '_, '_, 'info, Claim<'info>>,
+        owner: Pubkey,
+        token_mint: Pubkey,
+        _nonce: u64,
+        version: u64,
+        current_chain: Bytes32,
+        _from_chain: Bytes32,
+        _from_user: Bytes32,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.bridge_params.paused,
+            BridgeError::BridgePaused
+        );
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+
+        prepare_user_token_account(
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.associated_token_program.to_account_info(),
+        )?;
+
+        let amount = ctx.accounts.claimable_tx.amount;
+        let fee = ctx.accounts.claimable_tx.fee;
+        record_outflow(
+            &mut ctx.accounts.bridge_params,
+            ctx.accounts.bridge_token_account.amount,
+            ctx.accounts.token_mint.supply,
+            amount + fee,
+        )?;
+        settle_payout(&mut ctx.accounts.bridge_params, amount + fee);
+
+        // transfer
+
+        let version = version.to_be_bytes();
+        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+
+        let seeds = &[
+            version.as_ref(),
+            b"wallet".as_ref(),
+            owner.as_ref(),
+            token_mint.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        if ctx.accounts.bridge_params.burn_and_mint {
+            mint_to_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                amount + fee,
+            )?;
+        }
+
+        transfer_from_bridge(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.token_mint,
+            signer,
+            ctx.remaining_accounts,
+            amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                ctx.accounts.fee_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                ctx.remaining_accounts,
+                fee,
+            )?;
+        }
+        Ok(())
+    }
+
+    // set the params for delayed fulfills
+    // fulfills of more than release_threshold token have to be released after
+    // release_delay seconds, 0 disables the threshold
+    // the guardian is allowed to veto delayed fulfills
+    pub fn set_release_params(
+        ctx: Context<SetReleaseParams>,
+        _token_mint: Pubkey,
+        release_threshold: u64,
+        release_delay: i64,
+        guardian: Pubkey,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gt!(release_delay, 0, BridgeError::PeriodZero);
+
+        ctx.accounts.bridge_params.release_threshold = release_threshold;
+        ctx.accounts.bridge_params.release_delay = release_delay;
+        ctx.accounts.bridge_params.guardian = guardian;
+        Ok(())
+    }
+
+    // switch the instance between lock-and-release and burn-and-mint mode
+    // in burn-and-mint mode, send burns the bridged tokens and fulfills mint them,
+    // so the bridge does not have to hold liquidity. the bridge token account has to
+    // be the mint authority of the token
+    pub fn set_burn_and_mint(
+        ctx: Context<SetBurnAndMint>,
+        _token_mint: Pubkey,
+        burn_and_mint: bool,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require!(
+            !burn_and_mint
+                || ctx.accounts.token_mint.mint_authority
+                    == COption::Some(ctx.accounts.bridge_token_account.key()),
+            BridgeError::NotMintAuthority
+        );
+
+        ctx.accounts.bridge_params.burn_and_mint = burn_and_mint;
+        Ok(())
+    }
+
+    // set the max amount of token minted for fulfills from the chain in burn-and-mint mode
+    // the amount minted is reduced by sends to the chain
+    pub fn set_mint_cap(
+        ctx: Context<SetMintCap>,
+        _token_mint: Pubkey,
+        mint_cap: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+        _chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.chain_data.mint_cap = mint_cap;
+        Ok(())
+    }
+
+    // create a mint representing remote_token of remote_chain on Solana
+    // the mint address is derived from the remote token, and its mint authority is the bridge
+    // token account of the instance for the new mint, which the owner initializes afterwards
+    // and switches to burn-and-mint mode. name and symbol are null-terminated strings
+    pub fn create_wrapped_mint(
+        ctx: Context<CreateWrappedMint>,
+        _decimals: u8,
+        name: Bytes32,
+        symbol: Bytes32,
+        _version: u64,
+        _current_chain: Bytes32,
+        remote_chain: Bytes32,
+        remote_token: Bytes32,
+    ) -> Result<()> {
+        let wrapped_token = &mut ctx.accounts.wrapped_token;
+        wrapped_token.remote_chain = remote_chain;
+        wrapped_token.remote_token = remote_token;
+        wrapped_token.name = name;
+        wrapped_token.symbol = symbol;
+        Ok(())
+    }
+
+    // set how long refunded send txs are kept before anyone can close them
+    // 0 means only the owner can close send txs
+    pub fn set_send_tx_retention(
+        ctx: Context<SetSendTxRetention>,
+        _token_mint: Pubkey,
+        send_tx_retention: i64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        ctx.accounts.bridge_params.send_tx_retention = send_tx_retention;
+        Ok(())
+    }
+
+    // close a send tx and return its rent to the initiator
+    // the owner closes it once the tx is fulfilled on the destination chain, attesting that it is,
+    // anyone can close it after the retention period if it was refunded
+    // the send tx is emitted as an event so it stays in the logs
+    pub fn close_send_tx(
+        ctx: Context<CloseSendTx>,
+        owner: Pubkey,
+        _token_mint: Pubkey,
+        _global_nonce: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let send_tx = &ctx.accounts.send_tx;
+        if ctx.accounts.authority.key() != owner {
+            let send_tx_retention = ctx.accounts.bridge_params.send_tx_retention;
+            require!(
+                send_tx_retention > 0
+                    && Clock::get()?.unix_timestamp >= send_tx.timestamp + send_tx_retention,
+                BridgeError::SendTxRetained
+            );
+            // without the owner's attestation, an unrefunded tx might still be pending
+            require!(send_tx.refunded, BridgeError::NotRefunded);
+        }
+
+        emit!(SendTxClosed {
+            send_tx: send_tx.key(),
+            initiator: send_tx.initiator,
+            amount: send_tx.amount,
+            to: send_tx.to,
+            nonce: send_tx.nonce,
+            global_nonce: send_tx.global_nonce,
+            timestamp: send_tx.timestamp,
+            to_chain: send_tx.to_chain,
+            block: send_tx.block,
+            fee: send_tx.fee,
+            exchange_rate: send_tx.exchange_rate,
+            refunded: send_tx.refunded,
+            remote_token: send_tx.remote_token,
+            remote_bridge: send_tx.remote_bridge,
+        });
+        Ok(())
+    }
+
+    // close a send tx created before send txs were derived from the global nonce, and return its
+    // rent to the initiator. can only be called by the owner, who attests that the tx was fulfilled
+    // the send tx is emitted as an event, with the fields the legacy layout lacks set to 0
+    pub fn close_legacy_send_tx(
+        ctx: Context<CloseLegacySendTx>,
+        _token_mint: Pubkey,
+        _nonce: u64,
+        _version: u64,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        let send_tx = ctx.accounts.send_tx.to_account_info();
+        let legacy_send_tx = {
+            let data = send_tx.try_borrow_data()?;
+            require_eq!(
+                data.len(),
+                8 + LegacyBridgeSendTx::MAX_SIZE,
+                ErrorCode::AccountDidNotDeserialize
+            );
+            require!(
+                data[..8] == BridgeSendTx::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            LegacyBridgeSendTx::deserialize(&mut &data[8..])?
+        };
+
+        emit!(SendTxClosed {
+            send_tx: send_tx.key(),
+            initiator: legacy_send_tx.initiator,
+            amount: legacy_send_tx.amount,
+            to: legacy_send_tx.to,
+            nonce: legacy_send_tx.nonce,
+            global_nonce: 0,
+            timestamp: legacy_send_tx.timestamp,
+            to_chain: legacy_send_tx.to_chain,
+            block: legacy_send_tx.block,
+            fee: 0,
+            exchange_rate: 0,
+            refunded: false,
+            remote_token: Bytes32::default(),
+            remote_bridge: Bytes32::default(),
+        });
+        close_program_account(send_tx, ctx.accounts.initiator.to_account_info())
+    }
+
+    // refund a send tx that was not and will not be fulfilled on the destination chain,
+    // e.g. because the chain was disabled or the recipient address is invalid
+    // can only be called by the owner, who attests that the tx is not fulfilled
+    // if refund_fee is set, the fee is also returned from the fee account, which the owner has to control
+    pub fn refund<'info>(
+        ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+        _global_nonce: u64,
+        refund_fee: bool,
+        version: u64,
+        current_chain: Bytes32,
+    ) -> Result<()> {
+        let send_tx = &mut ctx.accounts.send_tx;
+        require!(!send_tx.refunded, BridgeError::AlreadyRefunded);
+        // the backend must not fulfill refunded txs
+        send_tx.refunded = true;
+
+        // the accounting of the send is reversed in the mode it was sent in,
+        // tokens burned by the send are minted again without applying the mint cap
+        if send_tx.burned {
+            ctx.accounts.to_chain_data.minted += send_tx.minted_burned;
+        } else {
+            settle_liabilities(
+                &mut ctx.accounts.bridge_params,
+                send_tx.amount * send_tx.exchange_rate,
+            );
+        }
+
+        let version = version.to_be_bytes();
+        let owner_key = ctx.accounts.owner.key();
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+
+        let seeds = &[
+            version.as_ref(),
+            b"wallet".as_ref(),
+            owner_key.as_ref(),
+            token_mint_key.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        if send_tx.burned {
+            mint_to_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                send_tx.amount * send_tx.exchange_rate,
+            )?;
+        }
+
+        transfer_from_bridge(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.token_mint,
+            signer,
+            ctx.remaining_accounts,
+            send_tx.amount * send_tx.exchange_rate,
+        )?;
+
+        if refund_fee && send_tx.fee > 0 {
+            transfer_tokens(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.fee_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.user_token_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+                ctx.accounts.token_mint.decimals,
+                &[],
+                ctx.remaining_accounts,
+                send_tx.fee * send_tx.exchange_rate,
+            )?;
+        }
+        Ok(())
+    }
+
+    // withdraw amount of tokens from the bridge to an allowed destination
+    // can only be called by the owner
+    // the bridge has to hold at least its liabilities and pending payouts afterwards, unless
+    // override_liabilities is set and the timelock started with request_withdraw_override has passed
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
+        amount: u64,
+        override_liabilities: bool,
+        version: u64,
+        current_chain: Bytes32,
+    ) -> Result<()> {
+        require_gt!(amount, 0, BridgeError::WithdrawZero);
+        check_withdraw_destination(&ctx.accounts.withdraw_destination)?;
+
+        // enforce the withdraw limit
+        let bridge_params = &mut ctx.accounts.bridge_params;
+        let limit_withdraw = bridge_params.limit_withdraw;
+        if limit_withdraw > 0 {
+            let period = bridge_params.limit_withdraw_period;
+            let now = Clock::get()?.unix_timestamp;
+            // periods are aligned to multiples of the period length, like the per-user send limit
+            let period_start = now - now % period;
+            if bridge_params.withdraw_period_start != period_start {
+                bridge_params.withdraw_period_start = period_start;
+                bridge_params.amount_withdrawn = 0;
+            }
+
+            require_gte!(
+                limit_withdraw,
+                bridge_params.amount_withdrawn + amount,
+                BridgeError::WithdrawLimitExceeded
+            );
+            bridge_params.amount_withdrawn += amount;
+        }
+
+        // keep the liabilities in the bridge unless the owner waited for the override
+        if override_liabilities {
+            use_withdraw_override(bridge_params)?;
+        } else {
+            require_gte!(
+                ctx.accounts
+                    .bridge_token_account
+                    .amount
+                    .saturating_sub(amount),
+                bridge_params.liabilities + bridge_params.pending_payouts,
+                BridgeError::WithdrawExceedsLiabilities
+            );
+        }
+
+        // transfer
+
+        let version = version.to_be_bytes();
+        let owner_key = ctx.accounts.owner.key();
+        let token_mint_key = ctx.accounts.token_mint.key();
+        let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+
+        let seeds = &[
+            version.as_ref(),
+            b"wallet".as_ref(),
+            owner_key.as_ref(),
+            token_mint_key.as_ref(),
+            current_chain.byte.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        transfer_from_bridge(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.bridge_token_account.to_account_info(),
+            ctx.accounts.withdraw_token_account.to_account_info(),
+            &ctx.accounts.token_mint,
+            signer,
+            ctx.remaining_accounts,
+            amount,
+        )?;
+        Ok(())
+    }
+
+    // send native SOL to another chain
+    // the lamports are moved into the bridge token account and the fee account of the native
+    // mint directly, so the user does not need a token account of the native mint
+    pub fn send_native(
+        ctx: Context<SendNative>,
+        _owner: Pubkey,
+        token_mint: Pubkey,
+        amount: u64,
+        to: Bytes32,
+        _version: u64,
+        to_chain: Bytes32,
+        _current_chain: Bytes32,
+    ) -> Result<()> {
+        require_keys_eq!(token_mint, native_mint::ID, BridgeError::NotNativeMint);
+        check_registry_paused(&ctx.accounts.registry, token_mint)?;
+        let (amount_taxed, fee) = send_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.to_chain_data,
+            &mut ctx.accounts.send_limit,
+            amount,
+        )?;
+
+        // wrap
+
+        let exchange_rate = ctx.accounts.to_chain_data.exchange_rate_from;
+        let amount_sent = amount_taxed * exchange_rate;
+        wrap_lamports(
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.bridge_token_account.to_account_info(),
+            amount_sent,
+        )?;
+        if fee > 0 {
+            wrap_lamports(
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.fee_account.to_account_info(),
+                fee * exchange_rate,
+            )?;
+        }
+
+        // the native mint has no mint authority, so the lamports are always locked
+        ctx.accounts.bridge_params.liabilities += amount_sent;
+        record_inflow(&mut ctx.accounts.bridge_params, amount_sent)?;
 
-        ctx.accounts.send_tx.initiator = ctx.accounts.user.key();
         ctx.accounts.send_tx.amount = amount_taxed;
-        ctx.accounts.send_tx.to = to;
-        ctx.accounts.send_tx.nonce = ctx.accounts.send_nonce.nonce;
-        // the timestemp is only used on the frontend
-        ctx.accounts.send_tx.timestamp = Clock::get()?.unix_timestamp;
-        ctx.accounts.send_tx.to_chain = to_chain;
-        ctx.accounts.send_tx.block = Clock::get()?.slot;
+        ctx.accounts.send_tx.fee = fee;
+        ctx.accounts.send_tx.exchange_rate = exchange_rate;
+        record_send(
+            &mut ctx.accounts.send_tx,
+            &mut ctx.accounts.send_nonce,
+            &mut ctx.accounts.bridge_params,
+            &ctx.accounts.to_chain_data,
+            ctx.accounts.user.key(),
+            to,
+            to_chain,
+        )
+    }
+
+    // send a bridge tx to another chain
+    pub fn send<'info>(
+        ctx: Context<'_, '_, '_, 'info, Send<'info>>,
+        owner: Pubkey,
+        token_mint: Pubkey,
+        amount: u64,
+        to: Bytes32,
+        version: u64,
+        to_chain: Bytes32,
+        current_chain: Bytes32,
+    ) -> Result<()> {
+        check_registry_paused(&ctx.accounts.registry, ctx.accounts.token_mint.key())?;
+        let (amount_taxed, fee) = send_amounts(
+            &ctx.accounts.bridge_params,
+            &ctx.accounts.to_chain_data,
+            &mut ctx.accounts.send_limit,
+            amount,
+        )?;
+
+        // transfer token to the bridge
+
+        let exchange_rate = ctx.accounts.to_chain_data.exchange_rate_from;
+        let amount_sent = amount_taxed * exchange_rate;
+        let balance_before = ctx.accounts.bridge_token_account.amount;
+        transfer_tokens(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.bridge_token_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(), // "from" authority
+            },
+            ctx.accounts.token_mint.decimals,
+            &[],
+            ctx.remaining_accounts,
+            amount_sent,
+        )?;
+
+        // the bridge receives less than sent if the token has a transfer fee
+        // like on the EVM side, the received amount is checked so tokens that behave
+        // differently can not make the bridge under-collateralized
+        ctx.accounts.bridge_token_account.reload()?;
+        let received = ctx
+            .accounts
+            .bridge_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(BridgeError::BadToken)?;
+        require_eq!(
+            received,
+            amount_sent - transfer_fee(&ctx.accounts.token_mint, amount_sent)?,
+            BridgeError::BadToken
+        );
+        // only the received amount is bridged, it has to convert without a remainder
+        // so no dust is left in the bridge
+        require_eq!(received % exchange_rate, 0, BridgeError::BadToken);
+        let amount_received = received / exchange_rate;
+        require_gt!(amount_received, 0, BridgeError::AmountTooLow);
+
+        // in burn-and-mint mode the bridged tokens are burned instead of locked
+        let burned = ctx.accounts.bridge_params.burn_and_mint;
+        let mut minted_burned = 0;
+        if burned {
+            let version = version.to_be_bytes();
+            let bump: u8 = *ctx.bumps.get("bridge_token_account").unwrap();
+            let seeds = &[
+                version.as_ref(),
+                b"wallet".as_ref(),
+                owner.as_ref(),
+                token_mint.as_ref(),
+                current_chain.byte.as_ref(),
+                &[bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            burn_from_bridge(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.bridge_token_account.to_account_info(),
+                &ctx.accounts.token_mint,
+                signer,
+                received,
+            )?;
+            // tokens that were not minted by the bridge can be burned as well
+            let to_chain_data = &mut ctx.accounts.to_chain_data;
+            minted_burned = to_chain_data.minted.min(received);
+            to_chain_data.minted -= minted_burned;
+        } else {
+            // the locked tokens have to stay in the bridge to honor fulfills back to Solana
+            ctx.accounts.bridge_params.liabilities += received;
+        }
+        record_inflow(&mut ctx.accounts.bridge_params, received)?;
+
+        // transfer fee to the fee account
+        if fee > 0 {
+            transfer_tokens(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.fee_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(), // "from" authority
+                },
+                ctx.accounts.token_mint.decimals,
+                &[],
+                ctx.remaining_accounts,
+                fee * exchange_rate,
+            )?;
+        }
+
+        ctx.accounts.send_tx.amount = amount_received;
+        ctx.accounts.send_tx.fee = fee;
+        ctx.accounts.send_tx.exchange_rate = exchange_rate;
+        ctx.accounts.send_tx.burned = burned;
+        ctx.accounts.send_tx.minted_burned = minted_burned;
+        record_send(
+            &mut ctx.accounts.send_tx,
+            &mut ctx.accounts.send_nonce,
+            &mut ctx.accounts.bridge_params,
+            &ctx.accounts.to_chain_data,
+            ctx.accounts.user.key(),
+            to,
+            to_chain,
+        )
+    }
+}
+
+// checks that the owner allowed the token account to withdraw to
+// destinations that were never allowed have no account
+fn check_withdraw_destination(withdraw_destination: &AccountInfo) -> Result<()> {
+    require!(
+        !withdraw_destination.data_is_empty(),
+        BridgeError::WithdrawDestinationNotAllowed
+    );
+    let withdraw_destination =
+        WithdrawDestination::try_deserialize(&mut &withdraw_destination.try_borrow_data()?[..])?;
+    require!(
+        withdraw_destination.allowed,
+        BridgeError::WithdrawDestinationNotAllowed
+    );
+    Ok(())
+}
+
+// checks that the instance of the token mint is not paused by the registry of the owner
+// owners without a registry pass its address without an account
+fn check_registry_paused(registry: &AccountInfo, token_mint: Pubkey) -> Result<()> {
+    if registry.data_is_empty() {
+        return Ok(());
+    }
+    let registry = Registry::try_deserialize(&mut &registry.try_borrow_data()?[..])?;
+    require!(
+        !registry.paused || !registry.mints.contains(&token_mint),
+        BridgeError::BridgePaused
+    );
+    Ok(())
+}
+
+// pauses or unpauses the bridge
+// unpausing starts a new outflow window, otherwise the circuit breaker
+// would pause the bridge again on the next fulfill
+fn set_paused(bridge_params: &mut BridgeParams, paused: bool) {
+    if bridge_params.paused && !paused {
+        bridge_params.outflow_window_start = 0;
+        bridge_params.outflow_window_amount = 0;
+    }
+    bridge_params.paused = paused;
+}
+
+// resizes an account of the program to space bytes, zeroing the new bytes
+// the payer tops up the rent of the account
+fn resize_account<'info>(
+    account: AccountInfo<'info>,
+    payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if rent > 0 {
+        let cpi_ctx = CpiContext::new(
+            system_program,
+            system_program::Transfer {
+                from: payer,
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(cpi_ctx, rent)?;
+    }
+    account.realloc(space, true).map_err(Into::into)
+}
+
+// closes an account of the program and sends its rent to the receiver
+// for accounts that can not be loaded with the close constraint
+fn close_program_account<'info>(
+    account: AccountInfo<'info>,
+    receiver: AccountInfo<'info>,
+) -> Result<()> {
+    let lamports = account.lamports();
+    **receiver.try_borrow_mut_lamports()? += lamports;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.assign(&system_program::ID);
+    account.realloc(0, false).map_err(Into::into)
+}
+
+// checks that a bridge tx from another chain can be fulfilled
+// remote_token and remote_bridge are the token and bridge contract the tx was sent from,
+// which have to match the route stored for the chain
+// returns the amount to send to the user and the fee
+fn fulfill_amounts(
+    bridge_params: &BridgeParams,
+    from_chain_data: &ChainData,
+    amount: u64,
+    deadline: i64,
+    remote_token: Bytes32,
+    remote_bridge: Bytes32,
+) -> Result<(u64, u64)> {
+    // the owner signs fulfills with a deadline, so they can not be submitted long after
+    require_gte!(
+        deadline,
+        Clock::get()?.unix_timestamp,
+        BridgeError::FulfillExpired
+    );
+    require!(!bridge_params.paused, BridgeError::BridgePaused);
+    require!(from_chain_data.enabled, BridgeError::ChainDisabled);
+    require!(
+        remote_token == from_chain_data.remote_token
+            && remote_bridge == from_chain_data.remote_bridge,
+        BridgeError::RouteMismatch
+    );
+
+    // the conversion is needed to account for decimal differences between chains
+    let amount_converted = amount * from_chain_data.exchange_rate_from;
+    require_gte!(
+        bridge_params.limit_fulfill,
+        amount_converted,
+        BridgeError::FulfillLimitExceeded
+    );
+    // can not overflow as fee_fulfill is < MAX_FEE
+    let fee = (u128::from(amount_converted) * u128::from(bridge_params.fee_fulfill)
+        / u128::from(MAX_FEE)) as u64;
+    let amount_taxed = amount_converted - fee;
+
+    require_gt!(amount_taxed, 0, BridgeError::AmountTooLow);
+    Ok((amount_taxed, fee))
+}
+
+// checks a send of amount to the chain and enforces the send limits
+// returns the amount after the fee and the fee, converted for the destination chain
+fn send_amounts(
+    bridge_params: &BridgeParams,
+    to_chain_data: &ChainData,
+    send_limit: &mut UserSendLimit,
+    amount: u64,
+) -> Result<(u64, u64)> {
+    require!(!bridge_params.paused, BridgeError::BridgePaused);
+    require!(to_chain_data.enabled, BridgeError::ChainDisabled);
+    require_gte!(
+        bridge_params.limit_send,
+        amount,
+        BridgeError::SendLimitExceeded
+    );
+
+    // prevent a small amount of token from being lost
+    require_eq!(
+        amount % to_chain_data.exchange_rate_from,
+        0,
+        BridgeError::AmountUneven
+    );
+
+    // make sure at least some fee will be taken from each bridge tx
+    require_gte!(
+        amount / to_chain_data.exchange_rate_from,
+        u64::from(MAX_FEE),
+        BridgeError::AmountTooLow
+    );
+
+    // enforce the per-user send limit
+    let limit_send_user = bridge_params.limit_send_user;
+    if limit_send_user > 0 && !send_limit.exempt {
+        let period = bridge_params.limit_send_user_period;
+        let now = Clock::get()?.unix_timestamp;
+        // periods are aligned to multiples of the period length, e.g. calendar days (UTC)
+        let period_start = now - now % period;
+        if send_limit.period_start != period_start {
+            send_limit.period_start = period_start;
+            send_limit.amount_sent = 0;
+        }
+
+        require_gte!(
+            limit_send_user,
+            send_limit.amount_sent + amount,
+            BridgeError::UserSendLimitExceeded
+        );
+        send_limit.amount_sent += amount;
+    }
+
+    // the conversion is needed to account for decimal differences between chains
+    let amount_converted = amount / to_chain_data.exchange_rate_from;
+    // can not overflow as fee_send is < MAX_FEE
+    let fee = (u128::from(amount_converted) * u128::from(bridge_params.fee_send)
+        / u128::from(MAX_FEE)) as u64;
+    Ok((amount_converted - fee, fee))
+}
+
+// writes the rest of the send tx, whose amounts are set by the caller, and increments the nonces
+fn record_send(
+    send_tx: &mut BridgeSendTx,
+    send_nonce: &mut UserNonce,
+    bridge_params: &mut BridgeParams,
+    to_chain_data: &ChainData,
+    initiator: Pubkey,
+    to: Bytes32,
+    to_chain: Bytes32,
+) -> Result<()> {
+    send_tx.initiator = initiator;
+    send_tx.to = to;
+    send_tx.nonce = send_nonce.nonce;
+    send_tx.global_nonce = bridge_params.send_nonce;
+    send_tx.remote_token = to_chain_data.remote_token;
+    send_tx.remote_bridge = to_chain_data.remote_bridge;
+    // the timestemp is only used on the frontend
+    send_tx.timestamp = Clock::get()?.unix_timestamp;
+    send_tx.to_chain = to_chain;
+    send_tx.block = Clock::get()?.slot;
+
+    // increment the nonces
+
+    send_nonce.nonce += 1;
+    bridge_params.send_nonce += 1;
+    Ok(())
+}
+
+// moves lamports of the user into a token account of the native mint
+// and syncs its token balance with its lamports
+fn wrap_lamports<'info>(
+    system_program: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    user: AccountInfo<'info>,
+    token_account: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let cpi_ctx = CpiContext::new(
+        system_program,
+        system_program::Transfer {
+            from: user,
+            to: token_account.clone(),
+        },
+    );
+    system_program::transfer(cpi_ctx, amount)?;
+
+    let cpi_ctx = CpiContext::new(
+        token_program,
+        SyncNative {
+            account: token_account,
+        },
+    );
+    token_interface::sync_native(cpi_ctx)
+}
+
+// marks the nonce of the receipt as fulfilled in its bitmap page, reverts if it already is
+// the receipt is emitted as an event, and stored in the receipt account if one was passed
+fn mark_fulfilled(
+    fulfilled_bitmap: &AccountLoader<FulfilledBitmap>,
+    fulfill_receipt: Option<&mut Account<FulfillReceipt>>,
+    receipt: TxFulfilled,
+) -> Result<()> {
+    // a newly created page has no discriminator until the end of the instruction
+    let mut bitmap = match fulfilled_bitmap.load_mut() {
+        Ok(bitmap) => bitmap,
+        Err(_) => fulfilled_bitmap.load_init()?,
+    };
+    let bit = (receipt.nonce % FulfilledBitmap::NONCES) as usize;
+    let mask = 1u8 << (bit % 8);
+    require!(
+        bitmap.bits[bit / 8] & mask == 0,
+        BridgeError::AlreadyFulfilled
+    );
+    bitmap.bits[bit / 8] |= mask;
+
+    if let Some(fulfill_receipt) = fulfill_receipt {
+        fulfill_receipt.source_tx_hash = receipt.source_tx_hash;
+        fulfill_receipt.source_block = receipt.source_block;
+        fulfill_receipt.amount = receipt.amount;
+        fulfill_receipt.recipient = receipt.recipient;
+        fulfill_receipt.slot = receipt.slot;
+    }
+    emit!(receipt);
+    Ok(())
+}
+
+// in burn-and-mint mode, tracks the amount minted for txs from the chain
+// and reverts once it exceeds the mint cap of the chain
+fn record_mint(
+    bridge_params: &BridgeParams,
+    chain_data: &mut ChainData,
+    amount: u64,
+) -> Result<()> {
+    if !bridge_params.burn_and_mint {
+        return Ok(());
+    }
+
+    chain_data.minted += amount;
+    require_gte!(
+        chain_data.mint_cap,
+        chain_data.minted,
+        BridgeError::MintCapExceeded
+    );
+    Ok(())
+}
+
+// tokens released to users on Solana no longer have to be held for fulfills
+// in lock-and-release mode, moves the amount of a delayed or claimable fulfill from the
+// liabilities to the pending payouts, so it stays in the bridge until it is paid out
+fn reserve_payout(bridge_params: &mut BridgeParams, amount: u64) {
+    if bridge_params.burn_and_mint {
+        return;
+    }
+    settle_liabilities(bridge_params, amount);
+    bridge_params.pending_payouts += amount;
+}
+
+// removes the amount of a released or claimed fulfill from the pending payouts
+// fulfills made before payouts were tracked were not reserved, so they can not go below 0
+fn settle_payout(bridge_params: &mut BridgeParams, amount: u64) {
+    if bridge_params.burn_and_mint {
+        return;
+    }
+    bridge_params.pending_payouts = bridge_params.pending_payouts.saturating_sub(amount);
+}
+
+// consumes the withdraw override, which can only be used once per request
+// and only within WITHDRAW_OVERRIDE_WINDOW seconds after it unlocked
+fn use_withdraw_override(bridge_params: &mut BridgeParams) -> Result<()> {
+    let unlock_time = bridge_params.withdraw_override_unlock;
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        unlock_time > 0 && now >= unlock_time,
+        BridgeError::WithdrawOverrideLocked
+    );
+    require_gt!(
+        unlock_time + WITHDRAW_OVERRIDE_WINDOW,
+        now,
+        BridgeError::WithdrawOverrideExpired
+    );
+    bridge_params.withdraw_override_unlock = 0;
+    Ok(())
+}
+
+// tokens the bridge was funded with are not part of the liabilities, so they can not go below 0
+fn settle_liabilities(bridge_params: &mut BridgeParams, amount: u64) {
+    bridge_params.liabilities = bridge_params.liabilities.saturating_sub(amount);
+}
+
+// tracks the amount of token leaving the bridge within the current outflow window
+// and pauses the bridge once it exceeds the circuit breaker limit
+// the tx that exceeds the limit is still executed, later ones fail until the owner unpauses
+// in burn-and-mint mode the bridge holds no liquidity, so the outflow is measured
+// against the token supply instead of the bridge balance
+fn record_outflow(
+    bridge_params: &mut BridgeParams,
+    bridge_balance: u64,
+    token_supply: u64,
+    amount: u64,
+) -> Result<()> {
+    if bridge_params.outflow_limit == 0 {
+        return Ok(());
+    }
+    let bridge_balance = if bridge_params.burn_and_mint {
+        token_supply
+    } else {
+        bridge_balance
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+    if now >= bridge_params.outflow_window_start + bridge_params.outflow_window {
+        bridge_params.outflow_window_start = now;
+        bridge_params.outflow_window_balance = bridge_balance;
+        bridge_params.outflow_window_amount = 0;
+    }
+    bridge_params.outflow_window_amount += amount;
+
+    // can not overflow as outflow_limit is <= MAX_FEE
+    let limit = (u128::from(bridge_params.outflow_window_balance)
+        * u128::from(bridge_params.outflow_limit)
+        / u128::from(MAX_FEE)) as u64;
+    if bridge_params.outflow_window_amount > limit {
+        bridge_params.paused = true;
+        emit!(CircuitBreakerTripped {
+            outflow: bridge_params.outflow_window_amount,
+            bridge_balance: bridge_params.outflow_window_balance,
+            window_start: bridge_params.outflow_window_start,
+        });
+    }
+    Ok(())
+}
+
+// tokens sent to the bridge within the current outflow window offset the outflow,
+// so the circuit breaker limits the net amount leaving the bridge
+fn record_inflow(bridge_params: &mut BridgeParams, amount: u64) -> Result<()> {
+    if bridge_params.outflow_limit == 0 {
+        return Ok(());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < bridge_params.outflow_window_start + bridge_params.outflow_window {
+        bridge_params.outflow_window_amount =
+            bridge_params.outflow_window_amount.saturating_sub(amount);
+    }
+    Ok(())
+}
+
+// checks that the token account to send tokens to belongs to the recipient and holds the bridged
+// token. it can be any such token account, including ones owned by off-curve addresses (PDAs).
+// if the account does not exist yet, it has to be the recipient's associated token account,
+// which is then created
+fn prepare_user_token_account<'info>(
+    user_token_account: AccountInfo<'info>,
+    user: AccountInfo<'info>,
+    token_mint: AccountInfo<'info>,
+    payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    associated_token_program: AccountInfo<'info>,
+) -> Result<()> {
+    if user_token_account.data_is_empty() {
+        require_keys_eq!(
+            user_token_account.key(),
+            get_associated_token_address_with_program_id(
+                user.key,
+                token_mint.key,
+                token_program.key
+            ),
+            BridgeError::BadUserTokenAccount
+        );
+
+        let cpi_ctx = CpiContext::new(
+            associated_token_program,
+            Create {
+                payer,
+                associated_token: user_token_account.clone(),
+                authority: user.clone(),
+                mint: token_mint.clone(),
+                system_program,
+                token_program,
+            },
+        );
+        anchor_spl::associated_token::create(cpi_ctx)?;
+    }
+
+    let token_account = InterfaceAccount::<TokenAccount>::try_from(&user_token_account)?;
+    require_keys_eq!(
+        token_account.mint,
+        token_mint.key(),
+        BridgeError::BadUserTokenAccount
+    );
+    require_keys_eq!(
+        token_account.owner,
+        user.key(),
+        BridgeError::BadUserTokenAccount
+    );
+    Ok(())
+}
+
+// transfer token from the bridge token account
+// signer has to contain the seeds of the bridge token account
+fn transfer_from_bridge<'info>(
+    token_program: AccountInfo<'info>,
+    bridge_token_account: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    token_mint: &InterfaceAccount<'info, Mint>,
+    signer: &[&[&[u8]]],
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    transfer_tokens(
+        token_program,
+        TransferChecked {
+            from: bridge_token_account.clone(),
+            mint: token_mint.to_account_info(),
+            to,
+            authority: bridge_token_account,
+        },
+        token_mint.decimals,
+        signer,
+        remaining_accounts,
+        amount,
+    )
+}
+
+// mint token to the bridge token account, which is the mint authority in burn-and-mint mode
+// the minted tokens are then paid out like in lock-and-release mode
+// signer has to contain the seeds of the bridge token account
+fn mint_to_bridge<'info>(
+    token_program: AccountInfo<'info>,
+    bridge_token_account: AccountInfo<'info>,
+    token_mint: &InterfaceAccount<'info, Mint>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    let mint_instruction = MintTo {
+        mint: token_mint.to_account_info(),
+        to: bridge_token_account.clone(),
+        authority: bridge_token_account,
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(token_program, mint_instruction, signer);
+
+    token_interface::mint_to(cpi_ctx, amount)
+}
+
+// burn token held by the bridge token account
+// signer has to contain the seeds of the bridge token account
+fn burn_from_bridge<'info>(
+    token_program: AccountInfo<'info>,
+    bridge_token_account: AccountInfo<'info>,
+    token_mint: &InterfaceAccount<'info, Mint>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    let burn_instruction = Burn {
+        mint: token_mint.to_account_info(),
+        from: bridge_token_account.clone(),
+        authority: bridge_token_account,
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(token_program, burn_instruction, signer);
+
+    token_interface::burn(cpi_ctx, amount)
+}
+
+// transfer token with transfer_checked, which works for mints of both token programs
+// signer is empty if the authority signs the tx
+// remaining_accounts are passed on to the token program, so Token-2022 can invoke
+// the transfer hook of the mint with the extra accounts it requires
+fn transfer_tokens<'info>(
+    token_program: AccountInfo<'info>,
+    accounts: TransferChecked<'info>,
+    decimals: u8,
+    signer: &[&[&[u8]]],
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        token_program.key,
+        accounts.from.key,
+        accounts.mint.key,
+        accounts.to.key,
+        accounts.authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    let mut account_infos = vec![
+        accounts.from,
+        accounts.mint,
+        accounts.to,
+        accounts.authority,
+    ];
+    for account in remaining_accounts {
+        instruction.accounts.push(AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        });
+        account_infos.push(account.clone());
+    }
+
+    invoke_signed(&instruction, &account_infos, signer).map_err(Into::into)
+}
+
+// the fee taken by the token program on transferring amount of the token
+// only Token-2022 mints with the transfer fee extension have one
+fn transfer_fee(token_mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = token_mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => Ok(transfer_fee_config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(ProgramError::InvalidArgument)?),
+        Err(_) => Ok(0),
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(fee_send: u16, fee_fulfill: u16, limit_send: u64, limit_fulfill: u64, paused: bool, _version: u64, _current_chain: Bytes32)]
+pub struct Initialize<'info> {
+    // id of the token used by the bridge instance
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        token::mint=token_mint,
+        token::authority=bridge_token_account,
+    )]
+    // token account that holds the bridge's tokens
+    // has to be topped up before the bridge can be used, unless it is in burn-and-mint mode
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    // owner of the bridge who can change params and withdraw tokens
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        constraint = fee_account.mint == token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + BridgeParams::MAX_SIZE,
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, fee_send: u16, fee_fulfill: u16, limit_send: u64, limit_fulfill: u64, paused: bool, _version: u64, _current_chain: Bytes32)]
+pub struct SetParams<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(
+        mut,
+        constraint = fee_account.mint == _token_mint.key(),
+    )]
+    // the new fee account
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _version: u64, _current_chain: Bytes32)]
+pub struct MigrateParams<'info> {
+    #[account(mut)]
+    // pays the rent for the resized account
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        owner = crate::ID,
+    )]
+    /// CHECK: bridge params account in the legacy layout, checked in migrate_params
+    pub bridge_params: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account that holds the bridge's tokens
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
+pub struct MigrateChainData<'info> {
+    #[account(mut)]
+    // pays the rent for the resized account
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            _chain.byte.as_ref(),
+        ],
+        bump,
+        owner = crate::ID,
+    )]
+    /// CHECK: chain data account in the legacy layout, checked in migrate_chain_data
+    pub chain_data: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_version: u64, _current_chain: Bytes32)]
+pub struct InitializeRegistry<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + Registry::MAX_SIZE,
+        bump,
+    )]
+    // account that stores the registered mints
+    pub registry: Box<Account<'info, Registry>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _version: u64, _current_chain: Bytes32)]
+pub struct RegisterMint<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the registry to add the mint to
+    pub registry: Box<Account<'info, Registry>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // params of the instance of the mint, which has to exist
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _version: u64, _current_chain: Bytes32)]
+pub struct UnregisterMint<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the registry to remove the mint from
+    pub registry: Box<Account<'info, Registry>>,
+}
+
+#[derive(Accounts)]
+#[instruction(paused: bool, _version: u64, _current_chain: Bytes32)]
+pub struct SetRegistryPaused<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the registry to pause or unpause
+    pub registry: Box<Account<'info, Registry>>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, outflow_limit: u16, outflow_window: i64, _version: u64, _current_chain: Bytes32)]
+pub struct SetCircuitBreaker<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, enabled: bool, exchange_rate_from: u64, nonce_per_user: bool, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
+pub struct SetChainData<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            _chain.byte.as_ref(),
+        ],
+        space = 8 + ChainData::MAX_SIZE,
+        bump,
+    )]
+    // the chain data account to change
+    pub chain_data: Account<'info, ChainData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, remote_token: Bytes32, remote_bridge: Bytes32, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
+pub struct SetRemoteRoute<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            _chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the chain data account to change
+    pub chain_data: Account<'info, ChainData>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, native_drop: u64, native_drop_fee: u64, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
+pub struct SetNativeDrop<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            _chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the chain data account to change
+    pub chain_data: Account<'info, ChainData>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, lamports: u64, _version: u64, _current_chain: Bytes32)]
+pub struct ManageSolVault<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"sol_vault".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // holds the lamports for native drops
+    pub sol_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, limit_send_user: u64, limit_send_user_period: i64, _version: u64, _current_chain: Bytes32)]
+pub struct SetUserSendLimit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _user: Pubkey, exempt: bool, _version: u64, _current_chain: Bytes32)]
+pub struct SetUserSendLimitExempt<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_limit".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _user.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + UserSendLimit::MAX_SIZE,
+        bump,
+    )]
+    // the send limit account of the user to change
+    pub send_limit: Account<'info, UserSendLimit>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, limit_withdraw: u64, limit_withdraw_period: i64, _version: u64, _current_chain: Bytes32)]
+pub struct SetWithdrawLimit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _version: u64, _current_chain: Bytes32)]
+pub struct RequestWithdrawOverride<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, withdraw_override_delay: i64, _version: u64, _current_chain: Bytes32)]
+pub struct SetWithdrawOverrideDelay<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _destination: Pubkey, allowed: bool, _version: u64, _current_chain: Bytes32)]
+pub struct SetWithdrawDestination<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"withdraw_destination".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _destination.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + WithdrawDestination::MAX_SIZE,
+        bump,
+    )]
+    // the withdraw destination account of the token account to change
+    pub withdraw_destination: Account<'info, WithdrawDestination>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, amount: u64, to: Bytes32, _version: u64, to_chain: Bytes32, _current_chain: Bytes32)]
+pub struct Send<'info> {
+    #[account(mut, address = _token_mint)]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_nonce".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            user.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + UserNonce::MAX_SIZE,
+        bump
+    )]
+    // stores the nonce for the user
+    // nonce is a number unique to each bridge tx by a user from a bridge instance
+    pub send_nonce: Account<'info, UserNonce>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_tx".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            bridge_params.send_nonce.to_be_bytes().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + BridgeSendTx::MAX_SIZE,
+        bump
+    )]
+    // stores the send tx data (amount, to, etc.)
+    // derived from the global nonce, so all send txs of the instance can be found sequentially
+    pub send_tx: Box<Account<'info, BridgeSendTx>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_limit".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            user.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + UserSendLimit::MAX_SIZE,
+        bump
+    )]
+    // tracks the amount sent by the user in the current period
+    pub send_limit: Box<Account<'info, UserSendLimit>>,
+    #[account(
+        mut,
+        token::mint = _token_mint,
+        token::authority = user,
+    )]
+    // token account to take tokens from
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to store tokens in
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == _token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            _owner.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            to_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the destination chain
+    pub to_chain_data: Box<Account<'info, ChainData>>,
+    #[account(mut)]
+    // the bridge user's account
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, amount: u64, to: Bytes32, _version: u64, to_chain: Bytes32, _current_chain: Bytes32)]
+// like Send, without a token account of the user, whose lamports are sent instead
+pub struct SendNative<'info> {
+    #[account(mut, address = _token_mint)]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_nonce".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            user.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + UserNonce::MAX_SIZE,
+        bump
+    )]
+    // stores the nonce for the user
+    // nonce is a number unique to each bridge tx by a user from a bridge instance
+    pub send_nonce: Account<'info, UserNonce>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_tx".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            bridge_params.send_nonce.to_be_bytes().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + BridgeSendTx::MAX_SIZE,
+        bump
+    )]
+    // stores the send tx data (amount, to, etc.)
+    // derived from the global nonce, so all send txs of the instance can be found sequentially
+    pub send_tx: Box<Account<'info, BridgeSendTx>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_limit".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            user.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + UserSendLimit::MAX_SIZE,
+        bump
+    )]
+    // tracks the amount sent by the user in the current period
+    pub send_limit: Box<Account<'info, UserSendLimit>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to store tokens in
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == _token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            _owner.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+            to_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the destination chain
+    pub to_chain_data: Box<Account<'info, ChainData>>,
+    #[account(mut)]
+    // the bridge user's account
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(_global_nonce: u64, refund_fee: bool, _version: u64, _current_chain: Bytes32)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"send_tx".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _global_nonce.to_be_bytes().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // the send tx to refund
+    pub send_tx: Account<'info, BridgeSendTx>,
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = send_tx.initiator,
+    )]
+    // token account of the initiator to refund the tokens to
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+    )]
+    // account that received the fee
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+            send_tx.to_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the destination chain of the send tx
+    pub to_chain_data: Account<'info, ChainData>,
+    // the bridge owner's account
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, override_liabilities: bool, _version: u64, _current_chain: Bytes32)]
+pub struct Withdraw<'info> {
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+    )]
+    // token account to send tokens to
+    pub withdraw_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"withdraw_destination".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            withdraw_token_account.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: marks the token account to send tokens to as an allowed destination,
+    /// checked in the instruction since it does not exist for destinations that were never allowed
+    pub withdraw_destination: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+
+    // the bridge owner's account
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce: u64, amount: u64, _to: Pubkey, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32, deadline: i64, source_tx_hash: Bytes32, source_block: u64)]
+pub struct Fulfill<'info> {
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled_bitmap".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            (_nonce / FulfilledBitmap::NONCES).to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            from_chain_data.replay_user(&_from_user),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfilledBitmap::MAX_SIZE,
+        bump
+    )]
+    // check double spend
+    // the page holding the nonce, its bit is set in mark_fulfilled
+    // which reverts the transaction if the nonce was already fulfilled
+    pub fulfilled_bitmap: AccountLoader<'info, FulfilledBitmap>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        constraint = legacy_fulfilled.data_is_empty() @ BridgeError::AlreadyFulfilled,
+    )]
+    /// CHECK: marks txs fulfilled before the sender was part of the replay key,
+    /// which must not be fulfilled again
+    pub legacy_fulfilled: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"receipt".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfillReceipt::MAX_SIZE,
+        bump
+    )]
+    // optional receipt of the fulfill kept on chain, written in mark_fulfilled
+    pub fulfill_receipt: Option<Box<Account<'info, FulfillReceipt>>>,
+    #[account(mut)]
+    /// CHECK: token account to send tokens to, checked in prepare_user_token_account
+    pub user_token_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+            _from_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the source chain
+    pub from_chain_data: Box<Account<'info, ChainData>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"sol_vault".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // holds the lamports for native drops
+    pub sol_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        address = _to,
+    )]
+    /// CHECK: the recipient's wallet, taken from the fulfilled tx
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    // pays the rent for the new accounts
+    pub payer: Signer<'info>,
+    // the bridge owner's account
+    pub owner: Signer<'info>,
 
-        // increment the nonce
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
 
-        ctx.accounts.send_nonce.nonce += 1;
-        Ok(())
-    }
+#[derive(Accounts)]
+#[instruction(_nonce: u64, amount: u64, _to: Pubkey, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32, deadline: i64, source_tx_hash: Bytes32, source_block: u64)]
+pub struct FulfillNative<'info> {
+    #[account(address = native_mint::ID @ BridgeError::NotNativeMint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled_bitmap".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            (_nonce / FulfilledBitmap::NONCES).to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            from_chain_data.replay_user(&_from_user),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfilledBitmap::MAX_SIZE,
+        bump
+    )]
+    // check double spend
+    // the page holding the nonce, its bit is set in mark_fulfilled
+    // which reverts the transaction if the nonce was already fulfilled
+    pub fulfilled_bitmap: AccountLoader<'info, FulfilledBitmap>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        constraint = legacy_fulfilled.data_is_empty() @ BridgeError::AlreadyFulfilled,
+    )]
+    /// CHECK: marks txs fulfilled before the sender was part of the replay key,
+    /// which must not be fulfilled again
+    pub legacy_fulfilled: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"receipt".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfillReceipt::MAX_SIZE,
+        bump
+    )]
+    // optional receipt of the fulfill kept on chain, written in mark_fulfilled
+    pub fulfill_receipt: Option<Box<Account<'info, FulfillReceipt>>>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"unwrap".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = unwrap_account,
+    )]
+    // temporary token account the wrapped SOL is unwrapped from, closed in the same tx
+    pub unwrap_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+            _from_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the source chain
+    pub from_chain_data: Box<Account<'info, ChainData>>,
+    #[account(
+        mut,
+        address = _to,
+    )]
+    /// CHECK: the recipient's wallet, taken from the fulfilled tx
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    // pays the rent for the new accounts and passes the unwrapped lamports on to the recipient
+    pub payer: Signer<'info>,
+    // the bridge owner's account
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-#[instruction(fee_send: u16, fee_fulfill: u16, limit_send: u64, paused: bool, _version: u64, _current_chain: Bytes32)]
-pub struct Initialize<'info> {
-    // id of the token used by the bridge instance
-    pub token_mint: Account<'info, Mint>,
+#[instruction(_nonce: u64, amount: u64, _to: Pubkey, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32, deadline: i64, source_tx_hash: Bytes32, source_block: u64)]
+pub struct FulfillDelayed<'info> {
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled_bitmap".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            (_nonce / FulfilledBitmap::NONCES).to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            from_chain_data.replay_user(&_from_user),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfilledBitmap::MAX_SIZE,
+        bump
+    )]
+    // check double spend
+    // the page holding the nonce, its bit is set in mark_fulfilled
+    // which reverts the transaction if the nonce was already fulfilled
+    pub fulfilled_bitmap: AccountLoader<'info, FulfilledBitmap>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        constraint = legacy_fulfilled.data_is_empty() @ BridgeError::AlreadyFulfilled,
+    )]
+    /// CHECK: marks txs fulfilled before the sender was part of the replay key,
+    /// which must not be fulfilled again
+    pub legacy_fulfilled: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"receipt".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfillReceipt::MAX_SIZE,
+        bump
+    )]
+    // optional receipt of the fulfill kept on chain, written in mark_fulfilled
+    pub fulfill_receipt: Option<Box<Account<'info, FulfillReceipt>>>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"pending_release".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + PendingRelease::MAX_SIZE,
+        bump
+    )]
+    // stores the tx until it is released
+    pub pending_release: Box<Account<'info, PendingRelease>>,
+    #[account(mut)]
+    /// CHECK: token account to send tokens to, checked in prepare_user_token_account
+    pub user_token_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+            _from_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the source chain
+    pub from_chain_data: Box<Account<'info, ChainData>>,
+    #[account(address = _to)]
+    /// CHECK: the recipient's wallet, taken from the fulfilled tx
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    // pays the rent for the new accounts
+    pub payer: Signer<'info>,
+    // the bridge owner's account
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce: u64, amount: u64, to: Pubkey, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32, deadline: i64, source_tx_hash: Bytes32, source_block: u64)]
+pub struct FulfillClaimable<'info> {
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled_bitmap".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            (_nonce / FulfilledBitmap::NONCES).to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            from_chain_data.replay_user(&_from_user),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfilledBitmap::MAX_SIZE,
+        bump
+    )]
+    // check double spend
+    // the page holding the nonce, its bit is set in mark_fulfilled
+    // which reverts the transaction if the nonce was already fulfilled
+    pub fulfilled_bitmap: AccountLoader<'info, FulfilledBitmap>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"fulfilled".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        constraint = legacy_fulfilled.data_is_empty() @ BridgeError::AlreadyFulfilled,
+    )]
+    /// CHECK: marks txs fulfilled before the sender was part of the replay key,
+    /// which must not be fulfilled again
+    pub legacy_fulfilled: UncheckedAccount<'info>,
     #[account(
         init,
-        payer = owner,
+        payer = payer,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"wallet".as_ref(),
+            b"receipt".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + FulfillReceipt::MAX_SIZE,
+        bump
+    )]
+    // optional receipt of the fulfill kept on chain, written in mark_fulfilled
+    pub fulfill_receipt: Option<Box<Account<'info, FulfillReceipt>>>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"claimable_tx".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        space = 8 + ClaimableTx::MAX_SIZE,
+        bump
+    )]
+    // stores the tx until it is claimed
+    pub claimable_tx: Box<Account<'info, ClaimableTx>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
             owner.key().as_ref(),
             token_mint.key().as_ref(),
             _current_chain.byte.as_ref(),
         ],
         bump,
-        token::mint=token_mint,
-        token::authority=bridge_token_account,
     )]
-    // token account that holds the bridge's tokens
-    // has to be topped up before the bridge can be used
-    pub bridge_token_account: Account<'info, TokenAccount>,
+    // account that stores params for this bridge instance
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            owner.key().as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"chain_data".as_ref(),
+            owner.key().as_ref(),
+            token_mint.key().as_ref(),
+            _current_chain.byte.as_ref(),
+            _from_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for the source chain
+    pub from_chain_data: Box<Account<'info, ChainData>>,
     #[account(mut)]
-    // owner of the bridge who can change params and withdraw tokens
+    // pays the rent for the new accounts
+    pub payer: Signer<'info>,
+    // the bridge owner's account
     pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, _nonce: u64, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32)]
+pub struct Claim<'info> {
+    #[account(mut, address = _token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
-        constraint = fee_account.mint == token_mint.key(),
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"claimable_tx".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        close = payer,
+        constraint = claimable_tx.recipient == user.key(),
+    )]
+    // the tx to claim
+    pub claimable_tx: Account<'info, ClaimableTx>,
+    #[account(mut)]
+    /// CHECK: token account to send tokens to, checked in prepare_user_token_account
+    pub user_token_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == _token_mint.key(),
     )]
     // account that receives the fees
-    pub fee_account: Account<'info, TokenAccount>,
+    pub fee_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
-        init,
-        payer = owner,
+        mut,
         seeds = [
             _version.to_be_bytes().as_ref(),
             b"bridge_params".as_ref(),
-            owner.key().as_ref(),
-            token_mint.key().as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
             _current_chain.byte.as_ref(),
         ],
-        space = 8 + BridgeParams::MAX_SIZE,
         bump,
     )]
     // account that stores params for this bridge instance
-    pub bridge_params: Account<'info, BridgeParams>,
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            _owner.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        address = claimable_tx.payer,
+    )]
+    /// CHECK: receives the rent of the claimable tx account
+    pub payer: UncheckedAccount<'info>,
+    #[account(mut)]
+    // the recipient of the tx
+    pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 #[derive(Accounts)]
-#[instruction(_token_mint: Pubkey, fee_send: u16, fee_fulfill: u16, limit_send: u64, paused: bool, _version: u64, _current_chain: Bytes32)]
-pub struct SetParams<'info> {
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, _nonce: u64, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32)]
+pub struct Release<'info> {
+    #[account(mut, address = _token_mint)]
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"pending_release".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        close = payer,
+    )]
+    // the delayed tx to release
+    pub pending_release: Account<'info, PendingRelease>,
+    #[account(
+        mut,
+        address = pending_release.user_token_account,
+    )]
+    // token account to send tokens to
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account to send tokens from
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
+        constraint = fee_account.mint == _token_mint.key(),
+    )]
+    // account that receives the fees
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"registry".as_ref(),
+            _owner.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: registry of the owner, which does not have to exist,
+    /// checked in check_registry_paused
+    pub registry: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        address = pending_release.payer,
+    )]
+    /// CHECK: receives the rent of the pending release account
+    pub payer: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, _nonce: u64, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32, _from_user: Bytes32)]
+pub struct Veto<'info> {
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"pending_release".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _nonce.to_be_bytes().as_ref(),
+            _from_chain.byte.as_ref(),
+            _from_user.byte.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        close = payer,
+    )]
+    // the delayed tx to cancel
+    pub pending_release: Account<'info, PendingRelease>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+        has_one = guardian,
+    )]
+    // account that stores params for this bridge instance
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(
+        mut,
+        address = pending_release.payer,
+    )]
+    /// CHECK: receives the rent of the pending release account
+    pub payer: UncheckedAccount<'info>,
+    // the guardian of the bridge instance
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, release_threshold: u64, release_delay: i64, guardian: Pubkey, _version: u64, _current_chain: Bytes32)]
+pub struct SetReleaseParams<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
     #[account(
@@ -375,22 +3859,47 @@ pub struct SetParams<'info> {
     )]
     // address of the bridge params account to change
     pub bridge_params: Account<'info, BridgeParams>,
-    #[account(
-        mut,
-        constraint = fee_account.mint == _token_mint.key(),
-    )]
-    // the new fee account
-    pub fee_account: Account<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-#[instruction(_token_mint: Pubkey, enabled: bool, exchange_rate_from: u64, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
-pub struct SetChainData<'info> {
-    #[account(mut)]
+#[instruction(_token_mint: Pubkey, burn_and_mint: bool, _version: u64, _current_chain: Bytes32)]
+pub struct SetBurnAndMint<'info> {
+    pub owner: Signer<'info>,
+    #[account(address = _token_mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // token account of the bridge, which has to be the mint authority
+    pub bridge_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            _version.to_be_bytes().as_ref(),
+            b"bridge_params".as_ref(),
+            owner.key().as_ref(),
+            _token_mint.as_ref(),
+            _current_chain.byte.as_ref(),
+        ],
+        bump,
+    )]
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, mint_cap: u64, _version: u64, _current_chain: Bytes32, _chain: Bytes32)]
+pub struct SetMintCap<'info> {
     pub owner: Signer<'info>,
     #[account(
-        init_if_needed,
-        payer = owner,
+        mut,
         seeds = [
             _version.to_be_bytes().as_ref(),
             b"chain_data".as_ref(),
@@ -399,230 +3908,151 @@ pub struct SetChainData<'info> {
             _current_chain.byte.as_ref(),
             _chain.byte.as_ref(),
         ],
-        space = 8 + ChainData::MAX_SIZE,
         bump,
     )]
     // the chain data account to change
     pub chain_data: Account<'info, ChainData>,
-
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(_owner: Pubkey, _token_mint: Pubkey, amount: u64, to: Bytes32, _version: u64, to_chain: Bytes32, _current_chain: Bytes32)]
-pub struct Send<'info> {
-    #[account(
-        init_if_needed,
-        payer = user,
-        seeds = [
-            _version.to_be_bytes().as_ref(),
-            b"send_nonce".as_ref(),
-            _owner.as_ref(),
-            _token_mint.as_ref(),
-            user.key().as_ref(),
-            _current_chain.byte.as_ref(),
-        ],
-        space = 8 + UserNonce::MAX_SIZE,
-        bump
-    )]
-    // stores the nonce for the user
-    // nonce is a number unique to each bridge tx by a user from a bridge instance
-    pub send_nonce: Account<'info, UserNonce>,
+#[instruction(_decimals: u8, name: Bytes32, symbol: Bytes32, _version: u64, _current_chain: Bytes32, _remote_chain: Bytes32, _remote_token: Bytes32)]
+pub struct CreateWrappedMint<'info> {
+    #[account(mut)]
+    // owner of the bridge instance that will use the mint
+    pub owner: Signer<'info>,
     #[account(
         init,
-        payer = user,
-        seeds = [
-            _version.to_be_bytes().as_ref(),
-            b"send_tx".as_ref(),
-            _owner.as_ref(),
-            _token_mint.as_ref(),
-            user.key().as_ref(),
-            send_nonce.nonce.to_be_bytes().as_ref(),
-            _current_chain.byte.as_ref(),
-        ],
-        space = 8 + BridgeSendTx::MAX_SIZE,
-        bump
-    )]
-    // stores the send tx data (amount, to, etc.)
-    pub send_tx: Account<'info, BridgeSendTx>,
-    #[account(
-        mut,
-        token::mint = _token_mint,
-        token::authority = user,
-    )]
-    // token account to take tokens from
-    pub user_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
+        payer = owner,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"wallet".as_ref(),
-            _owner.as_ref(),
-            _token_mint.as_ref(),
+            b"wrapped_mint".as_ref(),
+            owner.key().as_ref(),
             _current_chain.byte.as_ref(),
+            _remote_chain.byte.as_ref(),
+            _remote_token.byte.as_ref(),
         ],
         bump,
+        mint::decimals = _decimals,
+        mint::authority = bridge_token_account,
     )]
-    // token account to store tokens in
-    pub bridge_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
-        constraint = fee_account.mint == _token_mint.key(),
-    )]
-    // account that receives the fees
-    pub fee_account: Account<'info, TokenAccount>,
+    // the new mint
+    pub wrapped_mint: InterfaceAccount<'info, Mint>,
     #[account(
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"bridge_params".as_ref(),
-            _owner.as_ref(),
-            _token_mint.as_ref(),
+            b"wallet".as_ref(),
+            owner.key().as_ref(),
+            wrapped_mint.key().as_ref(),
             _current_chain.byte.as_ref(),
         ],
         bump,
     )]
-    // account that stores params for this bridge instance
-    pub bridge_params: Account<'info, BridgeParams>,
+    /// CHECK: address of the bridge token account of the instance for the new mint,
+    /// which is created by initialize
+    pub bridge_token_account: UncheckedAccount<'info>,
     #[account(
+        init,
+        payer = owner,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"chain_data".as_ref(),
-            _owner.as_ref(),
-            _token_mint.as_ref(),
+            b"wrapped_token".as_ref(),
+            owner.key().as_ref(),
+            wrapped_mint.key().as_ref(),
             _current_chain.byte.as_ref(),
-            to_chain.byte.as_ref(),
         ],
+        space = 8 + WrappedToken::MAX_SIZE,
         bump,
     )]
-    // account that stores params for the destination chain
-    pub to_chain_data: Account<'info, ChainData>,
-    #[account(mut)]
-    // the bridge user's account
-    pub user: Signer<'info>,
+    // stores the remote token the mint represents
+    pub wrapped_token: Account<'info, WrappedToken>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-#[instruction(_version: u64, _current_chain: Bytes32)]
-pub struct Withdraw<'info> {
-    pub token_mint: Account<'info, Mint>,
-
-    #[account(
-        mut,
-        token::mint = token_mint,
-    )]
-    // token account to send tokens to
-    pub withdraw_token_account: Account<'info, TokenAccount>,
-
+#[instruction(_token_mint: Pubkey, send_tx_retention: i64, _version: u64, _current_chain: Bytes32)]
+pub struct SetSendTxRetention<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
     #[account(
         mut,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"wallet".as_ref(),
+            b"bridge_params".as_ref(),
             owner.key().as_ref(),
-            token_mint.key().as_ref(),
+            _token_mint.as_ref(),
             _current_chain.byte.as_ref(),
         ],
         bump,
     )]
-    // token account to send tokens from
-    pub bridge_token_account: Account<'info, TokenAccount>,
-
-    // the bridge owner's account
-    pub owner: Signer<'info>,
-
-    pub token_program: Program<'info, Token>,
+    // address of the bridge params account to change
+    pub bridge_params: Account<'info, BridgeParams>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce: u64, amount: u64, _version: u64, _current_chain: Bytes32, _from_chain: Bytes32)]
-pub struct Fulfill<'info> {
-    pub token_mint: Account<'info, Mint>,
-    #[account(
-        init,
-        payer = user,
-        seeds = [
-            _version.to_be_bytes().as_ref(),
-            b"fulfilled".as_ref(),
-            owner.key().as_ref(),
-            token_mint.key().as_ref(),
-            _nonce.to_be_bytes().as_ref(),
-            _from_chain.byte.as_ref(),
-            _current_chain.byte.as_ref(),
-        ],
-        space = 8,
-        bump
-    )]
-    // check double spend
-    // The account can be empty because the mere existence of
-    // an account with the same seeds will revert the transaction
-    // on "empty_account" initialization.
-    pub empty_account: Account<'info, EmptyAccount>,
-    #[account(
-        init_if_needed,
-        payer = user,
-        associated_token::mint = token_mint,
-        associated_token::authority = user
-    )]
-    // token account to send tokens to
-    pub user_token_account: Account<'info, TokenAccount>,
+#[instruction(_owner: Pubkey, _token_mint: Pubkey, _global_nonce: u64, _version: u64, _current_chain: Bytes32)]
+pub struct CloseSendTx<'info> {
     #[account(
         mut,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"wallet".as_ref(),
-            owner.key().as_ref(),
-            token_mint.key().as_ref(),
+            b"send_tx".as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
+            _global_nonce.to_be_bytes().as_ref(),
             _current_chain.byte.as_ref(),
         ],
         bump,
+        has_one = initiator,
+        close = initiator,
     )]
-    // token account to send tokens from
-    pub bridge_token_account: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = fee_account.key() == bridge_params.fee_recipient.key(),
-        constraint = fee_account.mint == token_mint.key(),
-    )]
-    // account that receives the fees
-    pub fee_account: Box<Account<'info, TokenAccount>>,
+    // the send tx to close
+    pub send_tx: Account<'info, BridgeSendTx>,
     #[account(
         seeds = [
             _version.to_be_bytes().as_ref(),
             b"bridge_params".as_ref(),
-            owner.key().as_ref(),
-            token_mint.key().as_ref(),
+            _owner.as_ref(),
+            _token_mint.as_ref(),
             _current_chain.byte.as_ref(),
         ],
         bump,
     )]
     // account that stores params for this bridge instance
-    pub bridge_params: Box<Account<'info, BridgeParams>>,
+    pub bridge_params: Account<'info, BridgeParams>,
+    #[account(mut)]
+    /// CHECK: receives the rent, checked against the send tx
+    pub initiator: UncheckedAccount<'info>,
+    // the owner, or anyone after the retention period if the tx was refunded
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_token_mint: Pubkey, _nonce: u64, _version: u64, _current_chain: Bytes32)]
+pub struct CloseLegacySendTx<'info> {
     #[account(
+        mut,
         seeds = [
             _version.to_be_bytes().as_ref(),
-            b"chain_data".as_ref(),
+            b"send_tx".as_ref(),
             owner.key().as_ref(),
-            token_mint.key().as_ref(),
+            _token_mint.as_ref(),
+            initiator.key().as_ref(),
+            _nonce.to_be_bytes().as_ref(),
             _current_chain.byte.as_ref(),
-            _from_chain.byte.as_ref(),
         ],
         bump,
+        owner = crate::ID,
     )]
-    // account that stores params for the source chain
-    pub from_chain_data: Box<Account<'info, ChainData>>,
+    /// CHECK: send tx in the legacy layout, derived from the initiator and their nonce,
+    /// checked in close_legacy_send_tx
+    pub send_tx: UncheckedAccount<'info>,
     #[account(mut)]
-    // the bridge user's account
-    pub user: Signer<'info>,
+    /// CHECK: receives the rent, part of the address of the send tx
+    pub initiator: UncheckedAccount<'info>,
     // the bridge owner's account
     pub owner: Signer<'info>,
-
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 #[account]
@@ -637,6 +4067,33 @@ impl UserNonce {
     pub const MAX_SIZE: usize = 8;
 }
 
+#[account]
+#[derive(Default)]
+// marks a token account as a destination the owner is allowed to withdraw to
+pub struct WithdrawDestination {
+    pub allowed: bool,
+}
+
+impl WithdrawDestination {
+    pub const MAX_SIZE: usize = 1;
+}
+
+#[account]
+#[derive(Default)]
+// account that stores the amount of token sent by
+// a user from a bridge instance in the current period
+pub struct UserSendLimit {
+    // start of the current period, unix timestamp
+    pub period_start: i64,
+    pub amount_sent: u64,
+    // exempt users are not subject to the per-user send limit
+    pub exempt: bool,
+}
+
+impl UserSendLimit {
+    pub const MAX_SIZE: usize = 8 + 8 + 1;
+}
+
 #[account]
 #[derive(Default)]
 // stores data for a sent tx
@@ -645,13 +4102,46 @@ pub struct BridgeSendTx {
     pub amount: u64,
     // recipient address on the destination chain
     pub to: Bytes32,
+    // nonce of the tx among the txs sent by the initiator
     pub nonce: u64,
     pub timestamp: i64,
     pub to_chain: Bytes32,
     pub block: u64,
+    // nonce of the tx among all txs sent from the bridge instance
+    pub global_nonce: u64,
+    // fee taken on sending, in the same unit as amount
+    pub fee: u64,
+    // exchange rate of the destination chain at the time of sending
+    pub exchange_rate: u64,
+    // set once the tx is refunded, refunded txs must not be fulfilled
+    pub refunded: bool,
+    // token on the destination chain the tx is bridged to
+    pub remote_token: Bytes32,
+    // bridge contract on the destination chain that fulfills the tx
+    pub remote_bridge: Bytes32,
+    // whether the sent tokens were burned, i.e. the bridge was in burn-and-mint mode
+    pub burned: bool,
+    // part of the burned tokens that lowered the amount minted for the destination chain
+    pub minted_burned: u64,
 }
 
 impl BridgeSendTx {
+    pub const MAX_SIZE: usize = 32 + 8 + 32 + 8 + 8 + 32 + 8 + 8 + 8 + 8 + 1 + 32 + 32 + 1 + 8;
+}
+
+// layout of send txs before they were extended, only read by close_legacy_send_tx
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyBridgeSendTx {
+    pub initiator: Pubkey,
+    pub amount: u64,
+    pub to: Bytes32,
+    pub nonce: u64,
+    pub timestamp: i64,
+    pub to_chain: Bytes32,
+    pub block: u64,
+}
+
+impl LegacyBridgeSendTx {
     pub const MAX_SIZE: usize = 32 + 8 + 32 + 8 + 8 + 32 + 8;
 }
 
@@ -662,28 +4152,290 @@ pub struct BridgeParams {
     pub fee_fulfill: u16,
     // max send limit per tx
     pub limit_send: u64,
+    // max fulfill limit per tx
+    pub limit_fulfill: u64,
     pub fee_recipient: Pubkey,
     pub paused: bool,
+    // max amount a single user can send per period, 0 means no limit
+    pub limit_send_user: u64,
+    // length of the per-user send limit period in seconds
+    pub limit_send_user_period: i64,
+    // fulfills of more than this amount are released with a delay, 0 means no threshold
+    pub release_threshold: u64,
+    // delay of delayed fulfills in seconds
+    pub release_delay: i64,
+    // account allowed to veto delayed fulfills
+    pub guardian: Pubkey,
+    // max share of the bridge balance released per outflow window, 0 disables the circuit breaker
+    // stored with the same denominator as the fees (MAX_FEE)
+    pub outflow_limit: u16,
+    // length of the outflow window in seconds
+    pub outflow_window: i64,
+    // start of the current outflow window, unix timestamp
+    pub outflow_window_start: i64,
+    // bridge balance at the start of the current outflow window
+    pub outflow_window_balance: u64,
+    // net amount released within the current outflow window, sends within the window offset it
+    pub outflow_window_amount: u64,
+    // seconds after which anyone can close a refunded send tx, 0 means only the owner can
+    pub send_tx_retention: i64,
+    // nonce of the next tx sent from the bridge instance
+    pub send_nonce: u64,
+    // whether sent tokens are burned and fulfilled tokens minted instead of locked and released
+    pub burn_and_mint: bool,
+    // max amount the owner can withdraw per period, 0 means no limit
+    pub limit_withdraw: u64,
+    // length of the withdraw limit period in seconds
+    pub limit_withdraw_period: i64,
+    // start of the current withdraw limit period, unix timestamp
+    pub withdraw_period_start: i64,
+    // amount withdrawn in the current withdraw limit period
+    pub amount_withdrawn: u64,
+    // tokens locked by sends in lock-and-release mode and not released by fulfills yet,
+    // which the bridge has to hold to honor fulfills from other chains
+    pub liabilities: u64,
+    // time after which the owner can withdraw below the liabilities, 0 means not requested
+    pub withdraw_override_unlock: i64,
+    // timelock of the withdraw override in seconds, can only be lowered through the override
+    pub withdraw_override_delay: i64,
+    // tokens owed to delayed and claimable fulfills in lock-and-release mode,
+    // which the bridge has to hold until they are released, claimed or vetoed
+    pub pending_payouts: u64,
 }
 
 impl BridgeParams {
+    pub const MAX_SIZE: usize = 2
+        + 2
+        + 8
+        + 8
+        + 32
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + 32
+        + 2
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8;
+}
+
+// layout of the bridge params before they were extended, only read by migrate_params
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyBridgeParams {
+    pub fee_send: u16,
+    pub fee_fulfill: u16,
+    pub limit_send: u64,
+    pub fee_recipient: Pubkey,
+    pub paused: bool,
+}
+
+impl LegacyBridgeParams {
     pub const MAX_SIZE: usize = 2 + 2 + 8 + 32 + 1;
 }
 
+#[account]
+#[derive(Default)]
+// groups the instances of different mints of an owner, so they can be paused together
+pub struct Registry {
+    pub paused: bool,
+    // mints of the registered instances, in the order they were added
+    pub mints: Vec<Pubkey>,
+}
+
+impl Registry {
+    pub const MAX_MINTS: usize = 32;
+    pub const MAX_SIZE: usize = 1 + 4 + Registry::MAX_MINTS * 32;
+}
+
 #[account]
 #[derive(Default)]
 pub struct ChainData {
     pub enabled: bool,
     pub exchange_rate_from: u64,
+    // lamports sent to recipients of fulfills from the chain
+    pub native_drop: u64,
+    // extra fee taken for the native drop
+    pub native_drop_fee: u64,
+    // max amount of token minted for fulfills from the chain in burn-and-mint mode
+    pub mint_cap: u64,
+    // amount of token minted for fulfills from the chain and not burned by sends to it yet
+    pub minted: u64,
+    // address of the token on the chain
+    pub remote_token: Bytes32,
+    // address of the bridge contract on the chain
+    pub remote_bridge: Bytes32,
+    // whether the chain counts nonces per sender instead of one global nonce
+    pub nonce_per_user: bool,
 }
 
 impl ChainData {
-    pub const MAX_SIZE: usize = 1 + 32;
+    pub const MAX_SIZE: usize = 1 + 8 + 8 + 8 + 8 + 8 + 32 + 32 + 1;
+
+    // the sender is only part of the replay key of chains with per-user nonces,
+    // fulfills from chains with a global nonce share the bitmap pages of all senders
+    pub fn replay_user<'a>(&self, from_user: &'a Bytes32) -> &'a [u8] {
+        if self.nonce_per_user {
+            &from_user.byte
+        } else {
+            &[]
+        }
+    }
+}
+
+#[account]
+#[derive(Default)]
+// stores a delayed fulfill until it is released
+pub struct PendingRelease {
+    // token account to send tokens to
+    pub user_token_account: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    // unix timestamp after which the tokens can be released
+    pub unlock_time: i64,
+    // receives the rent once the account is closed
+    pub payer: Pubkey,
+}
+
+impl PendingRelease {
+    pub const MAX_SIZE: usize = 32 + 8 + 8 + 8 + 32;
+}
+
+#[account]
+#[derive(Default)]
+// stores a claimable fulfill until it is claimed
+pub struct ClaimableTx {
+    // wallet allowed to claim the tx
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    // receives the rent once the account is closed
+    pub payer: Pubkey,
+}
+
+impl ClaimableTx {
+    pub const MAX_SIZE: usize = 32 + 8 + 8 + 32;
+}
+
+#[account]
+#[derive(Default)]
+// minimal metadata of a mint created by create_wrapped_mint
+pub struct WrappedToken {
+    // chain the token is native to
+    pub remote_chain: Bytes32,
+    // address of the token on its chain
+    pub remote_token: Bytes32,
+    // null-terminated name of the token
+    pub name: Bytes32,
+    // null-terminated symbol of the token
+    pub symbol: Bytes32,
+}
+
+impl WrappedToken {
+    pub const MAX_SIZE: usize = 32 + 32 + 32 + 32;
+}
+
+#[account(zero_copy)]
+// marks a page of nonces of another chain as fulfilled, one bit per nonce
+// pages of chains with per-user nonces are kept per sender
+pub struct FulfilledBitmap {
+    pub bits: [u8; 128],
+}
+
+impl FulfilledBitmap {
+    pub const MAX_SIZE: usize = 128;
+    // nonces per page
+    pub const NONCES: u64 = 8 * 128;
 }
 
 #[account]
 #[derive(Default)]
-pub struct EmptyAccount {}
+// receipt of a fulfilled tx, mapping it to the tx on the source chain
+// only created if passed to the fulfill, the TxFulfilled event is always emitted
+pub struct FulfillReceipt {
+    // hash of the tx on the source chain
+    pub source_tx_hash: Bytes32,
+    // block of the tx on the source chain
+    pub source_block: u64,
+    // amount of the tx, before conversion and fees
+    pub amount: u64,
+    // wallet receiving the tokens
+    pub recipient: Pubkey,
+    // slot the tx was fulfilled in
+    pub slot: u64,
+}
+
+impl FulfillReceipt {
+    pub const MAX_SIZE: usize = 32 + 8 + 8 + 32 + 8;
+}
+
+#[event]
+// emitted for each tx from another chain marked as fulfilled
+// serves as the receipt mapping the fulfill to the tx on the source chain
+pub struct TxFulfilled {
+    pub from_chain: Bytes32,
+    pub from_user: Bytes32,
+    pub nonce: u64,
+    // amount of the tx, before conversion and fees
+    pub amount: u64,
+    // wallet receiving the tokens
+    pub recipient: Pubkey,
+    // hash of the tx on the source chain
+    pub source_tx_hash: Bytes32,
+    // block of the tx on the source chain
+    pub source_block: u64,
+    // slot the tx was fulfilled in
+    pub slot: u64,
+}
+
+#[event]
+// emitted when the circuit breaker pauses the bridge
+pub struct CircuitBreakerTripped {
+    // amount released within the outflow window
+    pub outflow: u64,
+    // bridge balance at the start of the outflow window
+    pub bridge_balance: u64,
+    pub window_start: i64,
+}
+
+#[event]
+// emitted when a send tx is closed, with the data of the closed send tx
+pub struct SendTxClosed {
+    pub send_tx: Pubkey,
+    pub initiator: Pubkey,
+    pub amount: u64,
+    pub to: Bytes32,
+    pub nonce: u64,
+    pub global_nonce: u64,
+    pub timestamp: i64,
+    pub to_chain: Bytes32,
+    pub block: u64,
+    pub fee: u64,
+    pub exchange_rate: u64,
+    pub refunded: bool,
+    pub remote_token: Bytes32,
+    pub remote_bridge: Bytes32,
+}
+
+#[event]
+// emitted when the owner requests to withdraw below the liabilities of the bridge
+pub struct WithdrawOverrideRequested {
+    pub liabilities: u64,
+    pub unlock_time: i64,
+}
 
 #[error_code]
 pub enum BridgeError {
@@ -696,4 +4448,34 @@ pub enum BridgeError {
     WithdrawZero,
     SendLimitExceeded,
     AmountUneven,
+    PeriodZero,
+    UserSendLimitExceeded,
+    FulfillLimitExceeded,
+    ReleaseDelayRequired,
+    ReleaseLocked,
+    OutflowLimitTooHigh,
+    BadUserTokenAccount,
+    NativeDropTooLow,
+    AlreadyFulfilled,
+    SendTxRetained,
+    AlreadyRefunded,
+    FulfillExpired,
+    BadToken,
+    NotMintAuthority,
+    MintCapExceeded,
+    NotNativeMint,
+    AlreadyRegistered,
+    RegistryFull,
+    RouteMismatch,
+    WithdrawLimitExceeded,
+    WithdrawDestinationNotAllowed,
+    WithdrawExceedsLiabilities,
+    WithdrawOverrideLocked,
+    AlreadyMigrated,
+    NotRefunded,
+    AmountBelowRent,
+    WithdrawOverrideExpired,
+    NonceModeChanged,
+    NotRegistered,
+    WithdrawOverrideDelayTooShort,
 }
@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{program::invoke_signed, system_instruction};
+
+declare_id!("4fWWkJDHntkEDv2XAQSnrq2hEjFkionshSs3v7Qkt22q");
+
+// discriminator of the Execute instruction of the transfer hook interface, also used as the
+// type of the list of its extra accounts: the first 8 bytes of
+// sha256("spl-transfer-hook-interface:execute")
+pub const EXECUTE_DISCRIMINATOR: [u8; 8] = [105, 37, 101, 197, 75, 251, 102, 26];
+
+// size of an ExtraAccountMeta in the list of extra accounts
+pub const EXTRA_ACCOUNT_META_SIZE: usize = 1 + 32 + 1 + 1;
+
+#[program]
+pub mod transfer_hook_mock {
+    use super::*;
+
+    // create the transfer counter of a mint and the list of extra accounts of its transfers,
+    // which requires the counter to be passed to each transfer
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        // tlv entry holding a list with a single fixed, writable account
+        let mut data = EXECUTE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&(4 + EXTRA_ACCOUNT_META_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(ctx.accounts.counter.key().as_ref());
+        data.push(0);
+        data.push(1);
+
+        let mint = ctx.accounts.mint.key();
+        let bump: u8 = *ctx.bumps.get("extra_account_metas").unwrap();
+        let seeds = &[b"extra-account-metas".as_ref(), mint.as_ref(), &[bump]];
+        invoke_signed(
+            &system_instruction::create_account(
+                ctx.accounts.payer.key,
+                ctx.accounts.extra_account_metas.key,
+                Rent::get()?.minimum_balance(data.len()),
+                data.len() as u64,
+                &crate::ID,
+            ),
+            &[
+                ctx.accounts.payer.to_account_info(),
+                ctx.accounts.extra_account_metas.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+        ctx.accounts
+            .extra_account_metas
+            .try_borrow_mut_data()?
+            .copy_from_slice(&data);
+        Ok(())
+    }
+
+    // the Execute instruction, called by the token program on each transfer of the mint
+    // the accounts are the source, mint, destination, authority, the list of extra accounts
+    // and the extra accounts, so the counter comes last
+    pub fn fallback(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> Result<()> {
+        require!(
+            data.len() >= 8 && data[..8] == EXECUTE_DISCRIMINATOR,
+            ErrorCode::InstructionFallbackNotFound
+        );
+        let counter = accounts.get(5).ok_or(ErrorCode::AccountNotEnoughKeys)?;
+        require_keys_eq!(
+            *counter.owner,
+            *program_id,
+            ErrorCode::AccountOwnedByWrongProgram
+        );
+
+        let mut counter_data = counter.try_borrow_mut_data()?;
+        let mut transfer_counter = TransferCounter::try_deserialize(&mut &counter_data[..])?;
+        transfer_counter.transfers += 1;
+        transfer_counter.try_serialize(&mut &mut counter_data[..])
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: the mint using this program as its transfer hook
+    pub mint: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"counter".as_ref(), mint.key().as_ref()],
+        space = 8 + TransferCounter::MAX_SIZE,
+        bump
+    )]
+    pub counter: Account<'info, TransferCounter>,
+    #[account(
+        mut,
+        seeds = [b"extra-account-metas".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: list of the extra accounts of the transfers, created in initialize
+    pub extra_account_metas: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(Default)]
+// counts the transfers of a mint
+pub struct TransferCounter {
+    pub transfers: u64,
+}
+
+impl TransferCounter {
+    pub const MAX_SIZE: usize = 8;
+}
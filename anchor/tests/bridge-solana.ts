@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { BridgeSolana } from "../target/types/bridge_solana";
+import { TransferHookMock } from "../target/types/transfer_hook_mock";
 import * as spl from "@solana/spl-token";
 import { PublicKey } from "@solana/web3.js";
 import { LAMPORTS_PER_SOL } from "@solana/web3.js";
@@ -28,6 +29,11 @@ function ethAddress(ethAddress: string) {
   return { byte: arr };
 }
 
+// encodes a string as a null-terminated Bytes32
+function stringToBytes(str: string) {
+  return { byte: Array.from(Buffer.from(str.padEnd(32, "\0"), "ascii")) };
+}
+
 function getFileLengths(dir: string, files: string[]): Record<string, number> {
   const lengths: Record<string, number> = {};
 
@@ -58,6 +64,11 @@ function folderDiff(
   return finalDiff;
 }
 
+// deadline for fulfills that are submitted right away
+function inAnHour() {
+  return new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+}
+
 function sleep(ms: number) {
   return new Promise((resolve) => setTimeout(resolve, ms));
 }
@@ -143,7 +154,15 @@ const TO_CHAIN2 = "evm.1337";
 const TO_CHAIN2_BUFFER = Buffer.from(TO_CHAIN2.padEnd(32, "\0"), "ascii");
 const TO_CHAIN2_B32 = { byte: Array.from(TO_CHAIN2_BUFFER) };
 const TEST_ETH_ADDRESS = "0xeF16b7ECC977276CEb7f3c9689200bF9DF54a8c5";
+const FROM_USER_B32 = ethAddress(TEST_ETH_ADDRESS);
+const FROM_USER_BUFFER = Buffer.from(FROM_USER_B32.byte);
+const NONCES_PER_PAGE = 8 * 128;
 const VERSION = new anchor.BN(0);
+// source chain tx of the fulfills
+const SOURCE_TX_HASH_B32 = { byte: Array.from(Buffer.alloc(32, 0xab)) };
+const SOURCE_BLOCK = new anchor.BN(1234);
+// remote token and bridge of routes that have none set
+const NO_REMOTE_ADDRESS_B32 = { byte: Array.from(Buffer.alloc(32)) };
 
 describe("bridge-solana", () => {
   // Configure the client to use the local cluster.
@@ -151,6 +170,8 @@ describe("bridge-solana", () => {
   anchor.setProvider(provider);
 
   const program = anchor.workspace.BridgeSolana as Program<BridgeSolana>;
+  const hookProgram = anchor.workspace
+    .TransferHookMock as Program<TransferHookMock>;
   const owner = new anchor.web3.Keypair();
 
   const createMint = async (): Promise<anchor.web3.PublicKey> => {
@@ -269,6 +290,18 @@ describe("bridge-solana", () => {
     );
   };
 
+  const getRegistryAccount = (bridgeOwner: anchor.web3.PublicKey) => {
+    return PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("registry"),
+        bridgeOwner.toBuffer(),
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+  };
+
   const getBridgeAccount = (
     name: string,
     bridgeOwner: anchor.web3.PublicKey,
@@ -304,11 +337,11 @@ describe("bridge-solana", () => {
     );
   };
 
+  // send txs are derived from the global nonce of the instance
   const getSendTxAccount = (
     bridgeOwner: anchor.web3.PublicKey,
     tokenMint: anchor.web3.PublicKey,
-    user: anchor.web3.PublicKey,
-    nonce: anchor.BN
+    globalNonce: anchor.BN
   ) => {
     return PublicKey.findProgramAddressSync(
       [
@@ -316,15 +349,60 @@ describe("bridge-solana", () => {
         Buffer.from("send_tx"),
         bridgeOwner.toBuffer(),
         tokenMint.toBuffer(),
-        user.toBuffer(),
+        globalNonce.toBuffer("be", 8),
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+  };
+
+  const getFulfillReceiptAccount = (
+    bridgeOwner: anchor.web3.PublicKey,
+    tokenMint: anchor.web3.PublicKey,
+    fromChain: Buffer,
+    fromUser: Buffer,
+    nonce: anchor.BN
+  ) => {
+    return PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("receipt"),
+        bridgeOwner.toBuffer(),
+        tokenMint.toBuffer(),
         nonce.toBuffer("be", 8),
+        fromChain,
+        fromUser,
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+  };
+
+  // the sender is only part of the key for chains with per-user nonces
+  const getFulfilledBitmapAccount = (
+    bridgeOwner: anchor.web3.PublicKey,
+    tokenMint: anchor.web3.PublicKey,
+    fromChain: Buffer,
+    nonce: anchor.BN,
+    fromUser = Buffer.alloc(0)
+  ) => {
+    return PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("fulfilled_bitmap"),
+        bridgeOwner.toBuffer(),
+        tokenMint.toBuffer(),
+        nonce.divn(NONCES_PER_PAGE).toBuffer("be", 8),
+        fromChain,
+        fromUser,
         CURRENT_CHAIN_BUFFER,
       ],
       program.programId
     );
   };
 
-  const getEmptyAccount = (
+  // marks txs fulfilled before the sender was part of the replay key
+  const getLegacyFulfilledAccount = (
     bridgeOwner: anchor.web3.PublicKey,
     tokenMint: anchor.web3.PublicKey,
     fromChain: Buffer,
@@ -348,6 +426,7 @@ describe("bridge-solana", () => {
   let bridgeTokenAccount: anchor.web3.PublicKey;
   let ownerAssociatedTokenAccount: anchor.web3.PublicKey;
   let bridgeParams: anchor.web3.PublicKey;
+  let solVault: anchor.web3.PublicKey;
   let toChainData: anchor.web3.PublicKey;
   let toChainData2: anchor.web3.PublicKey;
 
@@ -374,6 +453,7 @@ describe("bridge-solana", () => {
       owner.publicKey,
       tokenMint
     )[0];
+    solVault = getBridgeAccount("sol_vault", owner.publicKey, tokenMint)[0];
     toChainData = getChainDataAccount(
       owner.publicKey,
       tokenMint,
@@ -397,6 +477,7 @@ describe("bridge-solana", () => {
               10000,
               0,
               new anchor.BN(999999999),
+              new anchor.BN(999999999),
               false,
               VERSION,
               CURRENT_CHAIN_B32
@@ -422,6 +503,7 @@ describe("bridge-solana", () => {
               0,
               10000,
               new anchor.BN(999999999),
+              new anchor.BN(999999999),
               false,
               VERSION,
               CURRENT_CHAIN_B32
@@ -440,12 +522,19 @@ describe("bridge-solana", () => {
     ).to.be.true;
   });
 
-  function setChainData(chainB32, chainData, exchangeRate) {
+  function setChainData(
+    chainB32,
+    chainData,
+    exchangeRate,
+    noncePerUser = false,
+    mint = tokenMint
+  ) {
     return program.methods
       .setChainData(
-        tokenMint,
+        mint,
         true,
         exchangeRate,
+        noncePerUser,
         VERSION,
         CURRENT_CHAIN_B32,
         chainB32
@@ -464,6 +553,7 @@ describe("bridge-solana", () => {
         0,
         0,
         new anchor.BN(999999999),
+        new anchor.BN(999999999),
         false,
         VERSION,
         CURRENT_CHAIN_B32
@@ -504,51 +594,138 @@ describe("bridge-solana", () => {
   let user2: anchor.web3.Keypair;
   let userTokenAccount2: anchor.web3.PublicKey;
 
+  // creates the bridge instance of another mint, owned by the owner
+  // with no fees, and enables the chain TO_CHAIN for it
+  async function createInstance(
+    mint: PublicKey,
+    tokenProgram = spl.TOKEN_PROGRAM_ID,
+    {
+      limitSend = new anchor.BN(999999999),
+      limitFulfill = new anchor.BN(999999999),
+    } = {}
+  ) {
+    const feeAccount = await spl.createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      mint,
+      owner.publicKey,
+      {},
+      tokenProgram
+    );
+    const instance = {
+      feeAccount,
+      bridgeTokenAccount: getBridgeAccount("wallet", owner.publicKey, mint)[0],
+      bridgeParams: getBridgeAccount("bridge_params", owner.publicKey, mint)[0],
+      toChainData: getChainDataAccount(
+        owner.publicKey,
+        mint,
+        TO_CHAIN_BUFFER
+      )[0],
+    };
+    await program.methods
+      .initialize(
+        0,
+        0,
+        limitSend,
+        limitFulfill,
+        false,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        tokenMint: mint,
+        bridgeTokenAccount: instance.bridgeTokenAccount,
+        owner: owner.publicKey,
+        feeAccount,
+        bridgeParams: instance.bridgeParams,
+        tokenProgram,
+      })
+      .signers([owner])
+      .rpc();
+    await setChainData(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      new anchor.BN(1),
+      false,
+      mint
+    );
+    return instance;
+  }
+
+  // the mint of another instance and the token program it uses can be
+  // passed in the options, the main token is used by default
+  type InstanceOptions = {
+    mint?: PublicKey;
+    tokenProgram?: PublicKey;
+    amount?: anchor.BN;
+    remainingAccounts?: anchor.web3.AccountMeta[];
+  };
+
   async function send(
     toChainB32,
     toChainData_,
     user_,
     userTokenAccount_,
-    nonce
+    globalNonce,
+    {
+      mint = tokenMint,
+      tokenProgram = spl.TOKEN_PROGRAM_ID,
+      amount = new anchor.BN(133769),
+      remainingAccounts = [],
+    }: InstanceOptions = {}
   ) {
     const sendNonce = getBridgeUserAccount(
       "send_nonce",
       owner.publicKey,
-      tokenMint,
+      mint,
       user_.publicKey
     )[0];
-    const sendNonceAccount = await program.account.userNonce.fetchNullable(
-      sendNonce
-    );
-    // const nonce = sendNonceAccount?.nonce || new anchor.BN(0)
-    const sendTx = getSendTxAccount(
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
       owner.publicKey,
-      tokenMint,
-      user_.publicKey,
-      nonce
+      mint,
+      user_.publicKey
+    )[0];
+    const sendTx = getSendTxAccount(owner.publicKey, mint, globalNonce)[0];
+    const bridgeParams_ = getBridgeAccount(
+      "bridge_params",
+      owner.publicKey,
+      mint
     )[0];
+    const { feeRecipient } = await program.account.bridgeParams.fetch(
+      bridgeParams_
+    );
 
     return {
       tx: await program.methods
         .send(
           owner.publicKey,
-          tokenMint,
-          new anchor.BN(133769),
+          mint,
+          amount,
           ethAddress(TEST_ETH_ADDRESS),
           VERSION,
           toChainB32,
           CURRENT_CHAIN_B32
         )
         .accounts({
+          registry: getRegistryAccount(owner.publicKey)[0],
+          tokenMint: mint,
           sendTx,
           sendNonce,
+          sendLimit,
           userTokenAccount: userTokenAccount_,
-          bridgeTokenAccount,
+          bridgeTokenAccount: getBridgeAccount(
+            "wallet",
+            owner.publicKey,
+            mint
+          )[0],
           user: user_.publicKey,
-          feeAccount: ownerAssociatedTokenAccount,
+          feeAccount: feeRecipient,
           toChainData: toChainData_,
-          bridgeParams,
+          bridgeParams: bridgeParams_,
+          tokenProgram,
         })
+        .remainingAccounts(remainingAccounts)
         .signers([user_])
         .rpc({ skipPreflight: true }),
       sendNonce,
@@ -578,6 +755,12 @@ describe("bridge-solana", () => {
     expect(sendTxAccount.amount.eq(new anchor.BN(133769))).to.eq(true);
     expect(sendTxAccount.to.byte).to.deep.eq(ethAddress(TEST_ETH_ADDRESS).byte);
     expect(sendTxAccount.nonce.eq(new anchor.BN(0))).to.eq(true);
+    expect(sendTxAccount.globalNonce.eq(new anchor.BN(0))).to.eq(true);
+    expect(
+      (await program.account.bridgeParams.fetch(bridgeParams)).sendNonce.eq(
+        new anchor.BN(1)
+      )
+    ).to.eq(true);
   });
 
   it("should prevent sending with the same nonce", async () => {
@@ -596,14 +779,14 @@ describe("bridge-solana", () => {
     ).to.be.true;
   });
 
-  it("sends allow to use the same nonce for a different user", async () => {
+  it("sends with the next global nonce for a different user", async () => {
     [user2, userTokenAccount2] = await createUserAndAssociatedWallet(tokenMint);
     const { tx, sendNonce, sendTx } = await send(
       TO_CHAIN_B32,
       toChainData,
       user2,
       userTokenAccount2,
-      new anchor.BN(0)
+      new anchor.BN(1)
     );
 
     expect(
@@ -620,6 +803,7 @@ describe("bridge-solana", () => {
     expect(sendTxAccount.amount.eq(new anchor.BN(133769))).to.eq(true);
     expect(sendTxAccount.to.byte).to.deep.eq(ethAddress(TEST_ETH_ADDRESS).byte);
     expect(sendTxAccount.nonce.eq(new anchor.BN(0))).to.eq(true);
+    expect(sendTxAccount.globalNonce.eq(new anchor.BN(1))).to.eq(true);
 
     expect(
       await revertsWithLog(
@@ -629,7 +813,7 @@ describe("bridge-solana", () => {
             toChainData,
             user2,
             userTokenAccount2,
-            new anchor.BN(0)
+            new anchor.BN(1)
           ),
         ["send_tx", "A seeds constraint was violated"]
       )
@@ -642,30 +826,81 @@ describe("bridge-solana", () => {
     toChainDataAcc,
     user_: anchor.web3.Keypair,
     userTokenAccount_: PublicKey,
-    nonce: anchor.BN
+    nonce: anchor.BN,
+    remoteToken = NO_REMOTE_ADDRESS_B32,
+    remoteBridge = NO_REMOTE_ADDRESS_B32,
+    {
+      mint = tokenMint,
+      tokenProgram = spl.TOKEN_PROGRAM_ID,
+      amount = new anchor.BN(1337),
+      remainingAccounts = [],
+    }: InstanceOptions = {}
   ) {
-    const amount = new anchor.BN(1337);
+    const bridgeParams_ = getBridgeAccount(
+      "bridge_params",
+      owner.publicKey,
+      mint
+    )[0];
+    const { feeRecipient } = await program.account.bridgeParams.fetch(
+      bridgeParams_
+    );
 
     const instruction = await program.methods
-      .fulfill(nonce, amount, VERSION, CURRENT_CHAIN_B32, toChainB32)
+      .fulfill(
+        nonce,
+        amount,
+        user_.publicKey,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        toChainB32,
+        FROM_USER_B32,
+        inAnHour(),
+        SOURCE_TX_HASH_B32,
+        SOURCE_BLOCK,
+        remoteToken,
+        remoteBridge
+      )
       .accounts({
-        tokenMint,
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint: mint,
         userTokenAccount: userTokenAccount_,
-        bridgeTokenAccount,
+        bridgeTokenAccount: getBridgeAccount(
+          "wallet",
+          owner.publicKey,
+          mint
+        )[0],
+        solVault: getBridgeAccount("sol_vault", owner.publicKey, mint)[0],
         user: user_.publicKey,
+        payer: user_.publicKey,
         owner: owner.publicKey,
-        feeAccount: ownerAssociatedTokenAccount,
-        bridgeParams,
-        emptyAccount: getEmptyAccount(
+        feeAccount: feeRecipient,
+        bridgeParams: bridgeParams_,
+        fulfilledBitmap: getFulfilledBitmapAccount(
           owner.publicKey,
-          tokenMint,
+          mint,
+          toChainBuffer,
+          nonce
+        )[0],
+        legacyFulfilled: getLegacyFulfilledAccount(
+          owner.publicKey,
+          mint,
           toChainBuffer,
           nonce
         )[0],
         fromChainData: toChainDataAcc,
+        tokenProgram,
       })
+      .remainingAccounts(remainingAccounts)
       .instruction();
 
+    return signByOwnerAndSend(instruction, user_);
+  }
+
+  // the owner co-signs fulfill transactions before they are sent by the user
+  async function signByOwnerAndSend(
+    instruction: anchor.web3.TransactionInstruction,
+    user_: anchor.web3.Keypair
+  ) {
     const tx = new anchor.web3.Transaction();
     tx.add(instruction);
     tx.feePayer = user_.publicKey;
@@ -724,7 +959,7 @@ describe("bridge-solana", () => {
             userTokenAccount,
             new anchor.BN(1)
           ),
-        ["Allocate: account", "already in use"]
+        ["AlreadyFulfilled"]
       )
     ).to.be.true;
   });
@@ -741,7 +976,7 @@ describe("bridge-solana", () => {
             userTokenAccount2,
             new anchor.BN(1)
           ),
-        ["Allocate: account", "already in use"]
+        ["AlreadyFulfilled"]
       )
     ).to.be.true;
   });
@@ -769,7 +1004,7 @@ describe("bridge-solana", () => {
             userTokenAccount,
             new anchor.BN(2)
           ),
-        ["Allocate: account", "already in use"]
+        ["AlreadyFulfilled"]
       )
     ).to.be.true;
     expect(
@@ -783,30 +1018,67 @@ describe("bridge-solana", () => {
             userTokenAccount2,
             new anchor.BN(2)
           ),
-        ["Allocate: account", "already in use"]
+        ["AlreadyFulfilled"]
       )
     ).to.be.true;
   });
 
-  function withdraw(owner_?: anchor.web3.Keypair) {
+  function withdraw(
+    amount: anchor.BN,
+    owner_?: anchor.web3.Keypair,
+    overrideLiabilities = false
+  ) {
     if (!owner_) owner_ = owner;
 
     return program.methods
-      .withdraw(VERSION, CURRENT_CHAIN_B32)
+      .withdraw(amount, overrideLiabilities, VERSION, CURRENT_CHAIN_B32)
       .accounts({
         tokenMint,
         bridgeTokenAccount,
         owner: owner_.publicKey,
         withdrawTokenAccount: userTokenAccount,
+        withdrawDestination: getBridgeUserAccount(
+          "withdraw_destination",
+          owner_.publicKey,
+          tokenMint,
+          userTokenAccount
+        )[0],
+        bridgeParams: getBridgeAccount(
+          "bridge_params",
+          owner_.publicKey,
+          tokenMint
+        )[0],
       })
       .signers([owner_])
       .rpc();
   }
 
+  function setWithdrawDestination(destination: PublicKey, allowed: boolean) {
+    return program.methods
+      .setWithdrawDestination(
+        tokenMint,
+        destination,
+        allowed,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        withdrawDestination: getBridgeUserAccount(
+          "withdraw_destination",
+          owner.publicKey,
+          tokenMint,
+          destination
+        )[0],
+      })
+      .signers([owner])
+      .rpc();
+  }
+
   it("should not withdraw from another wallet", async () => {
     expect(
       await revertsWithErr(
-        () => withdraw(user),
+        () => withdraw(new anchor.BN(1), user),
         [
           "AnchorError caused by account: bridge_token_account. Error Code: ConstraintSeeds. Error Number: 2006. Error Message: A seeds constraint was violated.",
         ]
@@ -814,13 +1086,131 @@ describe("bridge-solana", () => {
     ).to.be.true;
   });
 
+  it("should only withdraw to allowed destinations", async () => {
+    expect(
+      await revertsWithErr(
+        () => withdraw(new anchor.BN(1)),
+        ["WithdrawDestinationNotAllowed"]
+      )
+    ).to.be.true;
+
+    await setWithdrawDestination(userTokenAccount, false);
+    expect(
+      await revertsWithErr(
+        () => withdraw(new anchor.BN(1)),
+        ["WithdrawDestinationNotAllowed"]
+      )
+    ).to.be.true;
+
+    await setWithdrawDestination(userTokenAccount, true);
+  });
+
+  function requestWithdrawOverride() {
+    return program.methods
+      .requestWithdrawOverride(tokenMint, VERSION, CURRENT_CHAIN_B32)
+      .accounts({ owner: owner.publicKey, bridgeParams })
+      .signers([owner])
+      .rpc();
+  }
+
+  function setWithdrawOverrideDelay(withdrawOverrideDelay: anchor.BN) {
+    return program.methods
+      .setWithdrawOverrideDelay(
+        tokenMint,
+        withdrawOverrideDelay,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({ owner: owner.publicKey, bridgeParams })
+      .signers([owner])
+      .rpc();
+  }
+
+  it("should not withdraw below the liabilities without an override", async () => {
+    const bridgeAccount = await spl.getAccount(
+      provider.connection,
+      bridgeTokenAccount
+    );
+    const amount = new anchor.BN(bridgeAccount.amount.toString());
+
+    expect(
+      await revertsWithErr(
+        () => withdraw(amount),
+        ["WithdrawExceedsLiabilities"]
+      )
+    ).to.be.true;
+    expect(
+      await revertsWithErr(
+        () => withdraw(amount, owner, true),
+        ["WithdrawOverrideLocked"]
+      )
+    ).to.be.true;
+
+    // the override is timelocked by its own delay, which starts at the minimum
+    expect(
+      (await program.account.bridgeParams.fetch(bridgeParams))
+        .withdrawOverrideDelay.toNumber()
+    ).to.eq(2);
+    expect(
+      await revertsWithErr(
+        () => setWithdrawOverrideDelay(new anchor.BN(1)),
+        ["WithdrawOverrideDelayTooShort"]
+      )
+    ).to.be.true;
+    await requestWithdrawOverride();
+    expect(
+      await revertsWithErr(
+        () => withdraw(amount, owner, true),
+        ["WithdrawOverrideLocked"]
+      )
+    ).to.be.true;
+    await sleep(3000);
+  });
+
+  it("should only lower the withdraw override delay through the override", async () => {
+    // raising the delay takes effect immediately
+    await setWithdrawOverrideDelay(new anchor.BN(3));
+    expect(
+      await revertsWithErr(
+        () => setWithdrawOverrideDelay(new anchor.BN(2)),
+        ["WithdrawOverrideLocked"]
+      )
+    ).to.be.true;
+
+    await requestWithdrawOverride();
+    await sleep(4000);
+    await setWithdrawOverrideDelay(new anchor.BN(2));
+    expect(
+      (await program.account.bridgeParams.fetch(bridgeParams))
+        .withdrawOverrideDelay.toNumber()
+    ).to.eq(2);
+
+    // lowering the delay used up the override
+    expect(
+      await revertsWithErr(
+        () => withdraw(new anchor.BN(1), owner, true),
+        ["WithdrawOverrideLocked"]
+      )
+    ).to.be.true;
+    await requestWithdrawOverride();
+    await sleep(3000);
+  });
+
   it("should withdraw", async () => {
     const prevAccount = await spl.getAccount(
       provider.connection,
       userTokenAccount
     );
+    const bridgeAccount = await spl.getAccount(
+      provider.connection,
+      bridgeTokenAccount
+    );
 
-    const tx = await withdraw();
+    const tx = await withdraw(
+      new anchor.BN(bridgeAccount.amount.toString()),
+      owner,
+      true
+    );
     console.log("Your transaction signature", tx);
 
     const afterAccount = await spl.getAccount(
@@ -833,7 +1223,9 @@ describe("bridge-solana", () => {
   });
 
   it("should not withdraw zero", async () => {
-    expect(await revertsWithErr(withdraw, ["WithdrawZero"])).to.be.true;
+    expect(
+      await revertsWithErr(() => withdraw(new anchor.BN(0)), ["WithdrawZero"])
+    ).to.be.true;
   });
 
   it("should not withdraw from another wallet", async () => {
@@ -863,6 +1255,7 @@ describe("bridge-solana", () => {
               500,
               500,
               new anchor.BN(999999998),
+              new anchor.BN(999999999),
               false,
               VERSION,
               CURRENT_CHAIN_B32
@@ -889,6 +1282,7 @@ describe("bridge-solana", () => {
               10000,
               500,
               new anchor.BN(999999998),
+              new anchor.BN(999999999),
               false,
               VERSION,
               CURRENT_CHAIN_B32
@@ -912,6 +1306,7 @@ describe("bridge-solana", () => {
               500,
               10000,
               new anchor.BN(999999998),
+              new anchor.BN(999999999),
               false,
               VERSION,
               CURRENT_CHAIN_B32
@@ -935,6 +1330,7 @@ describe("bridge-solana", () => {
         500,
         500,
         new anchor.BN(133769),
+        new anchor.BN(999999999),
         false,
         VERSION,
         CURRENT_CHAIN_B32
@@ -955,6 +1351,12 @@ describe("bridge-solana", () => {
       tokenMint,
       user.publicKey
     )[0];
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
+      owner.publicKey,
+      tokenMint,
+      user.publicKey
+    )[0];
     const sendNonceAccount = await program.account.userNonce.fetchNullable(
       sendNonce
     );
@@ -962,8 +1364,7 @@ describe("bridge-solana", () => {
     const sendTx = getSendTxAccount(
       owner.publicKey,
       tokenMint,
-      user.publicKey,
-      nonce
+      await getGlobalSendNonce()
     )[0];
 
     const amount = new anchor.BN(133769);
@@ -981,8 +1382,11 @@ describe("bridge-solana", () => {
         CURRENT_CHAIN_B32
       )
       .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
         sendTx,
         sendNonce,
+        sendLimit,
         userTokenAccount,
         bridgeTokenAccount,
         user: user.publicKey,
@@ -1014,15 +1418,16 @@ describe("bridge-solana", () => {
       tokenMint,
       user.publicKey
     )[0];
-    const sendNonceAccount = await program.account.userNonce.fetchNullable(
-      sendNonce
-    );
-    const nonce = sendNonceAccount?.nonce || new anchor.BN(0);
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
+      owner.publicKey,
+      tokenMint,
+      user.publicKey
+    )[0];
     const sendTx = getSendTxAccount(
       owner.publicKey,
       tokenMint,
-      user.publicKey,
-      nonce
+      await getGlobalSendNonce()
     )[0];
 
     expect(
@@ -1039,8 +1444,11 @@ describe("bridge-solana", () => {
               CURRENT_CHAIN_B32
             )
             .accounts({
+              registry: getRegistryAccount(owner.publicKey)[0],
+              tokenMint,
               sendTx,
               sendNonce,
+              sendLimit,
               userTokenAccount,
               bridgeTokenAccount,
               user: user.publicKey,
@@ -1061,15 +1469,16 @@ describe("bridge-solana", () => {
       tokenMint,
       user.publicKey
     )[0];
-    const sendNonceAccount = await program.account.userNonce.fetchNullable(
-      sendNonce
-    );
-    const nonce = sendNonceAccount?.nonce || new anchor.BN(0);
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
+      owner.publicKey,
+      tokenMint,
+      user.publicKey
+    )[0];
     const sendTx = getSendTxAccount(
       owner.publicKey,
       tokenMint,
-      user.publicKey,
-      nonce
+      await getGlobalSendNonce()
     )[0];
 
     expect(
@@ -1086,8 +1495,11 @@ describe("bridge-solana", () => {
               CURRENT_CHAIN_B32
             )
             .accounts({
+              registry: getRegistryAccount(owner.publicKey)[0],
+              tokenMint,
               sendTx,
               sendNonce,
+              sendLimit,
               userTokenAccount,
               bridgeTokenAccount,
               user: user.publicKey,
@@ -1101,4 +1513,2290 @@ describe("bridge-solana", () => {
       )
     ).to.be.true;
   });
+
+  async function getGlobalSendNonce() {
+    return (await program.account.bridgeParams.fetch(bridgeParams)).sendNonce;
+  }
+
+  function setUserSendLimit(limit: anchor.BN, period: anchor.BN) {
+    return program.methods
+      .setUserSendLimit(tokenMint, limit, period, VERSION, CURRENT_CHAIN_B32)
+      .accounts({
+        owner: owner.publicKey,
+        bridgeParams,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+  }
+
+  it("should not set a zero per-user send limit period", async () => {
+    expect(
+      await revertsWithErr(
+        () => setUserSendLimit(new anchor.BN(133769), new anchor.BN(0)),
+        ["PeriodZero"]
+      )
+    ).to.be.true;
+  });
+
+  it("should enforce the per-user send limit", async () => {
+    await setUserSendLimit(new anchor.BN(133769), new anchor.BN(86400));
+
+    await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user2,
+      userTokenAccount2,
+      await getGlobalSendNonce()
+    );
+
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
+      owner.publicKey,
+      tokenMint,
+      user2.publicKey
+    )[0];
+    const sendLimitAccount = await program.account.userSendLimit.fetch(
+      sendLimit
+    );
+    expect(sendLimitAccount.amountSent.eq(new anchor.BN(133769))).to.eq(true);
+
+    const nonce = await getGlobalSendNonce();
+    expect(
+      await revertsWithErr(
+        () =>
+          send(TO_CHAIN_B32, toChainData, user2, userTokenAccount2, nonce),
+        ["UserSendLimitExceeded"]
+      )
+    ).to.be.true;
+  });
+
+  it("should let exempt users send over the per-user send limit", async () => {
+    const sendLimit = getBridgeUserAccount(
+      "send_limit",
+      owner.publicKey,
+      tokenMint,
+      user2.publicKey
+    )[0];
+
+    await program.methods
+      .setUserSendLimitExempt(
+        tokenMint,
+        user2.publicKey,
+        true,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        sendLimit,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+
+    await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user2,
+      userTokenAccount2,
+      await getGlobalSendNonce()
+    );
+
+    await setUserSendLimit(new anchor.BN(0), new anchor.BN(86400));
+  });
+
+  it("should not fulfill over the fulfill limit", async () => {
+    function setLimitFulfill(limitFulfill: anchor.BN) {
+      return program.methods
+        .setParams(
+          tokenMint,
+          500,
+          500,
+          new anchor.BN(133769),
+          limitFulfill,
+          false,
+          VERSION,
+          CURRENT_CHAIN_B32
+        )
+        .accounts({
+          bridgeParams,
+          owner: owner.publicKey,
+          feeAccount: ownerAssociatedTokenAccount,
+        })
+        .signers([owner])
+        .rpc({ skipPreflight: true });
+    }
+
+    await setLimitFulfill(new anchor.BN(1336));
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfill(
+            TO_CHAIN_B32,
+            TO_CHAIN_BUFFER,
+            toChainData,
+            user,
+            userTokenAccount,
+            new anchor.BN(3)
+          ),
+        ["FulfillLimitExceeded"]
+      )
+    ).to.be.true;
+    await setLimitFulfill(new anchor.BN(999999999));
+  });
+
+  const getPendingReleaseAccount = (fromChain: Buffer, nonce: anchor.BN) => {
+    return PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("pending_release"),
+        owner.publicKey.toBuffer(),
+        tokenMint.toBuffer(),
+        nonce.toBuffer("be", 8),
+        fromChain,
+        FROM_USER_BUFFER,
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+  };
+
+  function setReleaseParams(
+    releaseThreshold: anchor.BN,
+    releaseDelay: anchor.BN,
+    guardian: PublicKey
+  ) {
+    return program.methods
+      .setReleaseParams(
+        tokenMint,
+        releaseThreshold,
+        releaseDelay,
+        guardian,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        bridgeParams,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+  }
+
+  async function fulfillDelayed(nonce: anchor.BN) {
+    const instruction = await program.methods
+      .fulfillDelayed(
+        nonce,
+        new anchor.BN(1337),
+        user.publicKey,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32,
+        inAnHour(),
+        SOURCE_TX_HASH_B32,
+        SOURCE_BLOCK,
+        NO_REMOTE_ADDRESS_B32,
+        NO_REMOTE_ADDRESS_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        userTokenAccount,
+        user: user.publicKey,
+        payer: user.publicKey,
+        owner: owner.publicKey,
+        bridgeParams,
+        fulfilledBitmap: getFulfilledBitmapAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          nonce
+        )[0],
+        legacyFulfilled: getLegacyFulfilledAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          nonce
+        )[0],
+        pendingRelease: getPendingReleaseAccount(TO_CHAIN_BUFFER, nonce)[0],
+        fromChainData: toChainData,
+      })
+      .instruction();
+
+    return signByOwnerAndSend(instruction, user);
+  }
+
+  function release(nonce: anchor.BN) {
+    return program.methods
+      .release(
+        owner.publicKey,
+        tokenMint,
+        nonce,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        pendingRelease: getPendingReleaseAccount(TO_CHAIN_BUFFER, nonce)[0],
+        userTokenAccount,
+        bridgeTokenAccount,
+        feeAccount: ownerAssociatedTokenAccount,
+        bridgeParams,
+        payer: user.publicKey,
+      })
+      .rpc();
+  }
+
+  function veto(nonce: anchor.BN, guardian: anchor.web3.Keypair) {
+    return program.methods
+      .veto(
+        owner.publicKey,
+        tokenMint,
+        nonce,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32
+      )
+      .accounts({
+        pendingRelease: getPendingReleaseAccount(TO_CHAIN_BUFFER, nonce)[0],
+        bridgeParams,
+        payer: user.publicKey,
+        guardian: guardian.publicKey,
+      })
+      .signers([guardian])
+      .rpc();
+  }
+
+  it("should require a delay for fulfills over the release threshold", async () => {
+    await setReleaseParams(
+      new anchor.BN(1000),
+      new anchor.BN(2),
+      user2.publicKey
+    );
+
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfill(
+            TO_CHAIN_B32,
+            TO_CHAIN_BUFFER,
+            toChainData,
+            user,
+            userTokenAccount,
+            new anchor.BN(3)
+          ),
+        ["ReleaseDelayRequired"]
+      )
+    ).to.be.true;
+  });
+
+  it("should release a delayed fulfill after the delay", async () => {
+    const nonce = new anchor.BN(3);
+    await fulfillDelayed(nonce);
+
+    // the pending payout can not be withdrawn
+    const pendingRelease = await program.account.pendingRelease.fetch(
+      getPendingReleaseAccount(TO_CHAIN_BUFFER, nonce)[0]
+    );
+    const params = await program.account.bridgeParams.fetch(bridgeParams);
+    expect(
+      params.pendingPayouts.eq(pendingRelease.amount.add(pendingRelease.fee))
+    ).to.eq(true);
+    const balance = new anchor.BN(
+      (
+        await spl.getAccount(provider.connection, bridgeTokenAccount)
+      ).amount.toString()
+    );
+    const surplus = balance.sub(params.liabilities).sub(params.pendingPayouts);
+    expect(
+      await revertsWithErr(
+        () => withdraw(anchor.BN.max(surplus.addn(1), new anchor.BN(1))),
+        ["WithdrawExceedsLiabilities"]
+      )
+    ).to.be.true;
+
+    expect(await revertsWithErr(() => release(nonce), ["ReleaseLocked"])).to
+      .be.true;
+
+    await sleep(3000);
+
+    const prevAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    await release(nonce);
+    expect(
+      (
+        await program.account.bridgeParams.fetch(bridgeParams)
+      ).pendingPayouts.toNumber()
+    ).to.eq(0);
+    const afterAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    expect(afterAccount.amount - prevAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000))
+    );
+
+    expect(
+      await program.account.pendingRelease.fetchNullable(
+        getPendingReleaseAccount(TO_CHAIN_BUFFER, nonce)[0]
+      )
+    ).to.be.null;
+  });
+
+  it("should let only the guardian veto a delayed fulfill", async () => {
+    const nonce = new anchor.BN(4);
+    await fulfillDelayed(nonce);
+
+    expect(await revertsWithErr(() => veto(nonce, user), ["ConstraintHasOne"]))
+      .to.be.true;
+
+    await veto(nonce, user2);
+    expect(
+      (
+        await program.account.bridgeParams.fetch(bridgeParams)
+      ).pendingPayouts.toNumber()
+    ).to.eq(0);
+    await sleep(3000);
+    expect(
+      await revertsWithErr(() => release(nonce), ["AccountNotInitialized"])
+    ).to.be.true;
+
+    await setReleaseParams(new anchor.BN(0), new anchor.BN(2), user2.publicKey);
+  });
+
+  function setCircuitBreaker(outflowLimit: number, outflowWindow: anchor.BN) {
+    return program.methods
+      .setCircuitBreaker(
+        tokenMint,
+        outflowLimit,
+        outflowWindow,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        bridgeParams,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+  }
+
+  it("should not set a circuit breaker limit over 100%", async () => {
+    expect(
+      await revertsWithErr(
+        () => setCircuitBreaker(10001, new anchor.BN(3600)),
+        ["OutflowLimitTooHigh"]
+      )
+    ).to.be.true;
+  });
+
+  it("should pause the bridge when the circuit breaker trips", async () => {
+    // 0.01% of the bridge balance is less than one fulfill
+    await setCircuitBreaker(1, new anchor.BN(3600));
+
+    await fulfill(
+      TO_CHAIN_B32,
+      TO_CHAIN_BUFFER,
+      toChainData,
+      user,
+      userTokenAccount,
+      new anchor.BN(5)
+    );
+    expect((await program.account.bridgeParams.fetch(bridgeParams)).paused).to
+      .be.true;
+
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfill(
+            TO_CHAIN_B32,
+            TO_CHAIN_BUFFER,
+            toChainData,
+            user,
+            userTokenAccount,
+            new anchor.BN(6)
+          ),
+        ["BridgePaused"]
+      )
+    ).to.be.true;
+
+    // disabling the circuit breaker does not need a window
+    await setCircuitBreaker(0, new anchor.BN(0));
+    await program.methods
+      .setParams(
+        tokenMint,
+        500,
+        500,
+        new anchor.BN(133769),
+        new anchor.BN(999999999),
+        false,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        bridgeParams,
+        owner: owner.publicKey,
+        feeAccount: ownerAssociatedTokenAccount,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+    expect((await program.account.bridgeParams.fetch(bridgeParams)).paused).to
+      .be.false;
+  });
+
+  // the owner relays the tx and pays the rent
+  async function fulfillByOwner(
+    nonce: anchor.BN,
+    recipient: PublicKey,
+    recipientTokenAccount: PublicKey,
+    fromUser = FROM_USER_B32,
+    fromChainB32 = TO_CHAIN_B32,
+    fromChainData = toChainData,
+    fulfillReceipt: PublicKey | null = null
+  ) {
+    const fromChainBuffer = Buffer.from(fromChainB32.byte);
+    const { noncePerUser } = await program.account.chainData.fetch(
+      fromChainData
+    );
+    return program.methods
+      .fulfill(
+        nonce,
+        new anchor.BN(1337),
+        recipient,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        fromChainB32,
+        fromUser,
+        inAnHour(),
+        SOURCE_TX_HASH_B32,
+        SOURCE_BLOCK,
+        NO_REMOTE_ADDRESS_B32,
+        NO_REMOTE_ADDRESS_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        userTokenAccount: recipientTokenAccount,
+        bridgeTokenAccount,
+        solVault,
+        user: recipient,
+        payer: owner.publicKey,
+        owner: owner.publicKey,
+        feeAccount: ownerAssociatedTokenAccount,
+        bridgeParams,
+        fulfilledBitmap: getFulfilledBitmapAccount(
+          owner.publicKey,
+          tokenMint,
+          fromChainBuffer,
+          nonce,
+          noncePerUser ? Buffer.from(fromUser.byte) : undefined
+        )[0],
+        legacyFulfilled: getLegacyFulfilledAccount(
+          owner.publicKey,
+          tokenMint,
+          fromChainBuffer,
+          nonce
+        )[0],
+        fulfillReceipt,
+        fromChainData,
+      })
+      .signers([owner])
+      .rpc();
+  }
+
+  it("should fulfill to a recipient that does not sign the tx", async () => {
+    const recipient = new anchor.web3.Keypair();
+    const recipientTokenAccount = await spl.getAssociatedTokenAddress(
+      tokenMint,
+      recipient.publicKey
+    );
+
+    await fulfillByOwner(
+      new anchor.BN(6),
+      recipient.publicKey,
+      recipientTokenAccount
+    );
+
+    const recipientAccount = await spl.getAccount(
+      provider.connection,
+      recipientTokenAccount
+    );
+    expect(recipientAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000))
+    );
+  });
+
+  it("should fulfill to a non-associated token account of an off-curve recipient", async () => {
+    const recipient = PublicKey.findProgramAddressSync(
+      [Buffer.from("vault")],
+      anchor.web3.SystemProgram.programId
+    )[0];
+    const recipientTokenAccount = await spl.createAccount(
+      provider.connection,
+      owner,
+      tokenMint,
+      recipient,
+      new anchor.web3.Keypair()
+    );
+
+    await fulfillByOwner(new anchor.BN(7), recipient, recipientTokenAccount);
+
+    const recipientAccount = await spl.getAccount(
+      provider.connection,
+      recipientTokenAccount
+    );
+    expect(recipientAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000))
+    );
+  });
+
+  it("should not fulfill to a token account of another wallet", async () => {
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfillByOwner(new anchor.BN(8), user.publicKey, userTokenAccount2),
+        ["BadUserTokenAccount"]
+      )
+    ).to.be.true;
+  });
+
+  const getClaimableTxAccount = (fromChain: Buffer, nonce: anchor.BN) => {
+    return PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("claimable_tx"),
+        owner.publicKey.toBuffer(),
+        tokenMint.toBuffer(),
+        nonce.toBuffer("be", 8),
+        fromChain,
+        FROM_USER_BUFFER,
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+  };
+
+  function claim(
+    nonce: anchor.BN,
+    user_: anchor.web3.Keypair,
+    userTokenAccount_: PublicKey
+  ) {
+    return program.methods
+      .claim(
+        owner.publicKey,
+        tokenMint,
+        nonce,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        claimableTx: getClaimableTxAccount(TO_CHAIN_BUFFER, nonce)[0],
+        userTokenAccount: userTokenAccount_,
+        bridgeTokenAccount,
+        feeAccount: ownerAssociatedTokenAccount,
+        bridgeParams,
+        payer: owner.publicKey,
+        user: user_.publicKey,
+      })
+      .signers([user_])
+      .rpc();
+  }
+
+  it("should fulfill into a claimable account claimed by the recipient", async () => {
+    const nonce = new anchor.BN(9);
+    await program.methods
+      .fulfillClaimable(
+        nonce,
+        new anchor.BN(1337),
+        user2.publicKey,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32,
+        inAnHour(),
+        SOURCE_TX_HASH_B32,
+        SOURCE_BLOCK,
+        NO_REMOTE_ADDRESS_B32,
+        NO_REMOTE_ADDRESS_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        fulfilledBitmap: getFulfilledBitmapAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          nonce
+        )[0],
+        legacyFulfilled: getLegacyFulfilledAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          nonce
+        )[0],
+        claimableTx: getClaimableTxAccount(TO_CHAIN_BUFFER, nonce)[0],
+        bridgeParams,
+        fromChainData: toChainData,
+        payer: owner.publicKey,
+        owner: owner.publicKey,
+      })
+      .signers([owner])
+      .rpc();
+
+    expect(
+      await revertsWithErr(
+        () => claim(nonce, user, userTokenAccount),
+        ["ConstraintRaw"]
+      )
+    ).to.be.true;
+
+    const prevAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount2
+    );
+    await claim(nonce, user2, userTokenAccount2);
+    const afterAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount2
+    );
+    expect(afterAccount.amount - prevAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000))
+    );
+
+    expect(
+      await revertsWithErr(
+        () => claim(nonce, user2, userTokenAccount2),
+        ["AccountNotInitialized"]
+      )
+    ).to.be.true;
+  });
+
+  function setNativeDrop(nativeDrop: anchor.BN, nativeDropFee: anchor.BN) {
+    return program.methods
+      .setNativeDrop(
+        tokenMint,
+        nativeDrop,
+        nativeDropFee,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        chainData: toChainData,
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true });
+  }
+
+  it("should not set a native drop below the rent exemption", async () => {
+    expect(
+      await revertsWithErr(
+        () => setNativeDrop(new anchor.BN(1000), new anchor.BN(100)),
+        ["NativeDropTooLow"]
+      )
+    ).to.be.true;
+  });
+
+  it("should send a native drop with the fulfill", async () => {
+    await setNativeDrop(
+      new anchor.BN(LAMPORTS_PER_SOL / 100),
+      new anchor.BN(100)
+    );
+    await program.methods
+      .fundSolVault(
+        tokenMint,
+        new anchor.BN(LAMPORTS_PER_SOL),
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        solVault,
+      })
+      .signers([owner])
+      .rpc();
+
+    const recipient = new anchor.web3.Keypair();
+    const recipientTokenAccount = await spl.getAssociatedTokenAddress(
+      tokenMint,
+      recipient.publicKey
+    );
+    await fulfillByOwner(
+      new anchor.BN(10),
+      recipient.publicKey,
+      recipientTokenAccount
+    );
+
+    expect(await provider.connection.getBalance(recipient.publicKey)).to.eq(
+      LAMPORTS_PER_SOL / 100
+    );
+    const recipientAccount = await spl.getAccount(
+      provider.connection,
+      recipientTokenAccount
+    );
+    expect(recipientAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000) - 100)
+    );
+
+    await setNativeDrop(new anchor.BN(0), new anchor.BN(0));
+    await program.methods
+      .withdrawSolVault(
+        tokenMint,
+        new anchor.BN(LAMPORTS_PER_SOL - LAMPORTS_PER_SOL / 100),
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        solVault,
+      })
+      .signers([owner])
+      .rpc();
+    expect(await provider.connection.getBalance(solVault)).to.eq(0);
+  });
+
+  it("should skip a native drop that would leave the sol vault below the rent exemption", async () => {
+    const nativeDrop = LAMPORTS_PER_SOL / 100;
+    await setNativeDrop(new anchor.BN(nativeDrop), new anchor.BN(100));
+    const manageSolVault = (method: string, lamports: number) =>
+      program.methods[method](
+        tokenMint,
+        new anchor.BN(lamports),
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+        .accounts({
+          owner: owner.publicKey,
+          solVault,
+        })
+        .signers([owner])
+        .rpc();
+    await manageSolVault("fundSolVault", nativeDrop + 1);
+
+    const recipient = new anchor.web3.Keypair();
+    const recipientTokenAccount = await spl.getAssociatedTokenAddress(
+      tokenMint,
+      recipient.publicKey
+    );
+    await fulfillByOwner(
+      new anchor.BN(100),
+      recipient.publicKey,
+      recipientTokenAccount
+    );
+
+    expect(await provider.connection.getBalance(recipient.publicKey)).to.eq(0);
+    const recipientAccount = await spl.getAccount(
+      provider.connection,
+      recipientTokenAccount
+    );
+    expect(recipientAccount.amount).to.eq(
+      BigInt(1337 - Math.floor((1337 * 500) / 10000))
+    );
+
+    await setNativeDrop(new anchor.BN(0), new anchor.BN(0));
+    await manageSolVault("withdrawSolVault", nativeDrop + 1);
+  });
+
+  it("should not fulfill the same nonce from a different sender", async () => {
+    // like BridgeAssist, the chain counts one global nonce for all senders
+    const nonce = new anchor.BN(10);
+    const fromUser = ethAddress("0x000000000000000000000000000000000000dEaD");
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfillByOwner(nonce, user.publicKey, userTokenAccount, fromUser),
+        ["AlreadyFulfilled"]
+      )
+    ).to.be.true;
+  });
+
+  it("should fulfill the same nonce from a different sender of a chain with per-user nonces", async () => {
+    const perUserChainBuffer = Buffer.from(
+      "sol.mainnet".padEnd(32, "\0"),
+      "ascii"
+    );
+    const perUserChainB32 = { byte: Array.from(perUserChainBuffer) };
+    const perUserChainData = getChainDataAccount(
+      owner.publicKey,
+      tokenMint,
+      perUserChainBuffer
+    )[0];
+    await setChainData(
+      perUserChainB32,
+      perUserChainData,
+      new anchor.BN(1),
+      true
+    );
+    // the nonce mode can not be changed later
+    expect(
+      await revertsWithErr(
+        () =>
+          setChainData(
+            perUserChainB32,
+            perUserChainData,
+            new anchor.BN(1),
+            false
+          ),
+        ["NonceModeChanged"]
+      )
+    ).to.be.true;
+
+    const nonce = new anchor.BN(0);
+    const fromUser = ethAddress("0x000000000000000000000000000000000000dEaD");
+    await fulfillByOwner(
+      nonce,
+      user.publicKey,
+      userTokenAccount,
+      FROM_USER_B32,
+      perUserChainB32,
+      perUserChainData
+    );
+    await fulfillByOwner(
+      nonce,
+      user.publicKey,
+      userTokenAccount,
+      fromUser,
+      perUserChainB32,
+      perUserChainData
+    );
+
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfillByOwner(
+            nonce,
+            user.publicKey,
+            userTokenAccount,
+            fromUser,
+            perUserChainB32,
+            perUserChainData
+          ),
+        ["AlreadyFulfilled"]
+      )
+    ).to.be.true;
+  });
+
+  it("should track nonces of a chain in bitmap pages", async () => {
+    const nonce = new anchor.BN(NONCES_PER_PAGE + 1);
+    await fulfillByOwner(nonce, user.publicKey, userTokenAccount);
+
+    const page = getFulfilledBitmapAccount(
+      owner.publicKey,
+      tokenMint,
+      TO_CHAIN_BUFFER,
+      nonce
+    )[0];
+    const bitmap = await program.account.fulfilledBitmap.fetch(page);
+    expect(bitmap.bits[0]).to.eq(1 << 1);
+
+    // the first page is unchanged
+    const firstPage = await program.account.fulfilledBitmap.fetch(
+      getFulfilledBitmapAccount(
+        owner.publicKey,
+        tokenMint,
+        TO_CHAIN_BUFFER,
+        new anchor.BN(0)
+      )[0]
+    );
+    expect(firstPage.bits[0]).to.eq(0b11111110);
+    expect(firstPage.bits[1]).to.eq(0b00000110);
+  });
+
+  function closeSendTx(
+    initiator: PublicKey,
+    globalNonce: anchor.BN,
+    authority: anchor.web3.Keypair
+  ) {
+    return program.methods
+      .closeSendTx(
+        owner.publicKey,
+        tokenMint,
+        globalNonce,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        sendTx: getSendTxAccount(owner.publicKey, tokenMint, globalNonce)[0],
+        bridgeParams,
+        initiator,
+        authority: authority.publicKey,
+      })
+      .signers([authority])
+      .rpc();
+  }
+
+  function setSendTxRetention(sendTxRetention: anchor.BN) {
+    return program.methods
+      .setSendTxRetention(
+        tokenMint,
+        sendTxRetention,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        bridgeParams,
+      })
+      .signers([owner])
+      .rpc();
+  }
+
+  it("should close a send tx confirmed by the owner", async () => {
+    const nonce = await getGlobalSendNonce();
+    const { sendTx } = await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user,
+      userTokenAccount,
+      nonce
+    );
+    const rent = await provider.connection.getBalance(sendTx);
+    const prevBalance = await provider.connection.getBalance(user.publicKey);
+
+    await closeSendTx(user.publicKey, nonce, owner);
+
+    expect(await provider.connection.getAccountInfo(sendTx)).to.be.null;
+    expect(await provider.connection.getBalance(user.publicKey)).to.eq(
+      prevBalance + rent
+    );
+  });
+
+  it("should close a refunded send tx after the retention period", async () => {
+    const nonce = await getGlobalSendNonce();
+    const { sendTx } = await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user,
+      userTokenAccount,
+      nonce
+    );
+
+    expect(
+      await revertsWithErr(
+        () => closeSendTx(user.publicKey, nonce, user2),
+        ["SendTxRetained"]
+      )
+    ).to.be.true;
+
+    await setSendTxRetention(new anchor.BN(1));
+    await sleep(2000);
+    // the tx might still be fulfilled unless it was refunded
+    expect(
+      await revertsWithErr(
+        () => closeSendTx(user.publicKey, nonce, user2),
+        ["NotRefunded"]
+      )
+    ).to.be.true;
+
+    await refund(nonce, true);
+    const sendTxData = await program.account.bridgeSendTx.fetch(sendTx);
+    const tx = await closeSendTx(user.publicKey, nonce, user2);
+    expect(await provider.connection.getAccountInfo(sendTx)).to.be.null;
+
+    const txDetails = await provider.connection.getTransaction(tx, {
+      commitment: "confirmed",
+    });
+    const eventParser = new anchor.EventParser(
+      program.programId,
+      new anchor.BorshCoder(program.idl)
+    );
+    const events = [...eventParser.parseLogs(txDetails.meta.logMessages)];
+    const closed = events.find((e) => e.name == "SendTxClosed").data;
+    expect(closed.refunded).to.be.true;
+    expect((closed.fee as anchor.BN).eq(sendTxData.fee)).to.eq(true);
+    expect((closed.exchangeRate as anchor.BN).eq(sendTxData.exchangeRate)).to.eq(
+      true
+    );
+    expect((closed.remoteToken as any).byte).to.deep.eq(
+      sendTxData.remoteToken.byte
+    );
+    expect((closed.remoteBridge as any).byte).to.deep.eq(
+      sendTxData.remoteBridge.byte
+    );
+    await setSendTxRetention(new anchor.BN(0));
+  });
+
+  it("should not close a legacy send tx that does not exist", async () => {
+    const nonce = new anchor.BN(0);
+    const [legacySendTx] = PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("send_tx"),
+        owner.publicKey.toBuffer(),
+        tokenMint.toBuffer(),
+        user.publicKey.toBuffer(),
+        nonce.toBuffer("be", 8),
+        CURRENT_CHAIN_BUFFER,
+      ],
+      program.programId
+    );
+    expect(
+      await revertsWithErr(
+        () =>
+          program.methods
+            .closeLegacySendTx(tokenMint, nonce, VERSION, CURRENT_CHAIN_B32)
+            .accounts({
+              sendTx: legacySendTx,
+              initiator: user.publicKey,
+              owner: owner.publicKey,
+            })
+            .signers([owner])
+            .rpc(),
+        ["ConstraintOwner"]
+      )
+    ).to.be.true;
+  });
+
+  function refund(globalNonce: anchor.BN, refundFee: boolean) {
+    return program.methods
+      .refund(globalNonce, refundFee, VERSION, CURRENT_CHAIN_B32)
+      .accounts({
+        tokenMint,
+        sendTx: getSendTxAccount(owner.publicKey, tokenMint, globalNonce)[0],
+        userTokenAccount,
+        bridgeTokenAccount,
+        feeAccount: ownerAssociatedTokenAccount,
+        bridgeParams,
+        toChainData,
+        owner: owner.publicKey,
+      })
+      .signers([owner])
+      .rpc();
+  }
+
+  it("should refund a send tx with the fee", async () => {
+    const prevAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    const nonce = await getGlobalSendNonce();
+    const { sendTx } = await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user,
+      userTokenAccount,
+      nonce
+    );
+
+    await refund(nonce, true);
+
+    const account = await spl.getAccount(provider.connection, userTokenAccount);
+    expect(account.amount).to.eq(prevAccount.amount);
+    expect((await program.account.bridgeSendTx.fetch(sendTx)).refunded).to.be
+      .true;
+
+    expect(
+      await revertsWithErr(() => refund(nonce, true), ["AlreadyRefunded"])
+    ).to.be.true;
+  });
+
+  it("should refund a send tx without the fee", async () => {
+    const prevAccount = await spl.getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    const nonce = await getGlobalSendNonce();
+    await send(TO_CHAIN_B32, toChainData, user, userTokenAccount, nonce);
+
+    expect(
+      await revertsWithErr(
+        () =>
+          program.methods
+            .refund(nonce, false, VERSION, CURRENT_CHAIN_B32)
+            .accounts({
+              tokenMint,
+              sendTx: getSendTxAccount(owner.publicKey, tokenMint, nonce)[0],
+              userTokenAccount,
+              bridgeTokenAccount,
+              feeAccount: ownerAssociatedTokenAccount,
+              bridgeParams,
+              toChainData,
+              owner: user.publicKey,
+            })
+            .signers([user])
+            .rpc(),
+        ["ConstraintSeeds"]
+      )
+    ).to.be.true;
+
+    await refund(nonce, false);
+
+    const account = await spl.getAccount(provider.connection, userTokenAccount);
+    const fee = Math.floor((133769 * 500) / 10000);
+    expect(account.amount).to.eq(prevAccount.amount - BigInt(fee));
+  });
+
+  it("should not fulfill after the deadline", async () => {
+    const instruction = await program.methods
+      .fulfill(
+        new anchor.BN(11),
+        new anchor.BN(1337),
+        user.publicKey,
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        FROM_USER_B32,
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60),
+        SOURCE_TX_HASH_B32,
+        SOURCE_BLOCK,
+        NO_REMOTE_ADDRESS_B32,
+        NO_REMOTE_ADDRESS_B32
+      )
+      .accounts({
+        registry: getRegistryAccount(owner.publicKey)[0],
+        tokenMint,
+        userTokenAccount,
+        bridgeTokenAccount,
+        solVault,
+        user: user.publicKey,
+        payer: user.publicKey,
+        owner: owner.publicKey,
+        feeAccount: ownerAssociatedTokenAccount,
+        bridgeParams,
+        fulfilledBitmap: getFulfilledBitmapAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          new anchor.BN(11)
+        )[0],
+        legacyFulfilled: getLegacyFulfilledAccount(
+          owner.publicKey,
+          tokenMint,
+          TO_CHAIN_BUFFER,
+          new anchor.BN(11)
+        )[0],
+        fromChainData: toChainData,
+      })
+      .instruction();
+
+    expect(
+      await revertsWithErr(
+        () => signByOwnerAndSend(instruction, user),
+        ["FulfillExpired"]
+      )
+    ).to.be.true;
+  });
+
+  it("should emit a receipt with the source chain tx", async () => {
+    const nonce = new anchor.BN(12);
+    const tx = await fulfillByOwner(nonce, user.publicKey, userTokenAccount);
+
+    const txDetails = await provider.connection.getTransaction(tx, {
+      commitment: "confirmed",
+    });
+    const eventParser = new anchor.EventParser(
+      program.programId,
+      new anchor.BorshCoder(program.idl)
+    );
+    const events = [...eventParser.parseLogs(txDetails.meta.logMessages)];
+    const receipt = events.find((e) => e.name == "TxFulfilled").data;
+    expect((receipt.nonce as anchor.BN).eq(nonce)).to.eq(true);
+    expect((receipt.amount as anchor.BN).eq(new anchor.BN(1337))).to.eq(true);
+    expect((receipt.recipient as PublicKey).toBase58()).to.eq(
+      user.publicKey.toBase58()
+    );
+    expect((receipt.sourceTxHash as any).byte).to.deep.eq(
+      SOURCE_TX_HASH_B32.byte
+    );
+    expect((receipt.sourceBlock as anchor.BN).eq(SOURCE_BLOCK)).to.eq(true);
+    expect((receipt.slot as anchor.BN).toNumber()).to.eq(txDetails.slot);
+  });
+
+  it("should store a receipt of the fulfill if requested", async () => {
+    const nonce = new anchor.BN(14);
+    const [fulfillReceipt] = getFulfillReceiptAccount(
+      owner.publicKey,
+      tokenMint,
+      TO_CHAIN_BUFFER,
+      FROM_USER_BUFFER,
+      nonce
+    );
+    const tx = await fulfillByOwner(
+      nonce,
+      user.publicKey,
+      userTokenAccount,
+      FROM_USER_B32,
+      TO_CHAIN_B32,
+      toChainData,
+      fulfillReceipt
+    );
+
+    const txDetails = await provider.connection.getTransaction(tx, {
+      commitment: "confirmed",
+    });
+    const receipt = await program.account.fulfillReceipt.fetch(fulfillReceipt);
+    expect(receipt.sourceTxHash.byte).to.deep.eq(SOURCE_TX_HASH_B32.byte);
+    expect(receipt.sourceBlock.eq(SOURCE_BLOCK)).to.eq(true);
+    expect(receipt.amount.eq(new anchor.BN(1337))).to.eq(true);
+    expect(receipt.recipient.toBase58()).to.eq(user.publicKey.toBase58());
+    expect(receipt.slot.toNumber()).to.eq(txDetails.slot);
+  });
+
+  it("should send only the received amount of a Token-2022 mint with a transfer fee", async () => {
+    const mint = new anchor.web3.Keypair();
+    const mintLen = spl.getMintLen([spl.ExtensionType.TransferFeeConfig]);
+    const tx = new anchor.web3.Transaction();
+    tx.add(
+      anchor.web3.SystemProgram.createAccount({
+        programId: spl.TOKEN_2022_PROGRAM_ID,
+        space: mintLen,
+        fromPubkey: provider.wallet.publicKey,
+        newAccountPubkey: mint.publicKey,
+        lamports: await provider.connection.getMinimumBalanceForRentExemption(
+          mintLen
+        ),
+      }),
+      // 1% transfer fee
+      spl.createInitializeTransferFeeConfigInstruction(
+        mint.publicKey,
+        provider.wallet.publicKey,
+        provider.wallet.publicKey,
+        100,
+        BigInt(1000000000),
+        spl.TOKEN_2022_PROGRAM_ID
+      ),
+      spl.createInitializeMintInstruction(
+        mint.publicKey,
+        6,
+        provider.wallet.publicKey,
+        null,
+        spl.TOKEN_2022_PROGRAM_ID
+      )
+    );
+    await provider.sendAndConfirm(tx, [mint]);
+
+    const instance = await createInstance(
+      mint.publicKey,
+      spl.TOKEN_2022_PROGRAM_ID
+    );
+    const userTokenAccount_ = await spl.createAssociatedTokenAccount(
+      provider.connection,
+      user,
+      mint.publicKey,
+      user.publicKey,
+      {},
+      spl.TOKEN_2022_PROGRAM_ID
+    );
+    const mintToUser = (amount: number) =>
+      provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          spl.createMintToInstruction(
+            mint.publicKey,
+            userTokenAccount_,
+            provider.wallet.publicKey,
+            amount,
+            [],
+            spl.TOKEN_2022_PROGRAM_ID
+          )
+        )
+      );
+    await mintToUser(1000000);
+
+    const options = {
+      mint: mint.publicKey,
+      tokenProgram: spl.TOKEN_2022_PROGRAM_ID,
+    };
+    const { sendTx } = await send(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(0),
+      { ...options, amount: new anchor.BN(1000000) }
+    );
+
+    const bridgeAccount = await spl.getAccount(
+      provider.connection,
+      instance.bridgeTokenAccount,
+      undefined,
+      spl.TOKEN_2022_PROGRAM_ID
+    );
+    expect(bridgeAccount.amount).to.eq(BigInt(990000));
+    const sendTxAccount = await program.account.bridgeSendTx.fetch(sendTx);
+    expect(sendTxAccount.amount.eq(new anchor.BN(990000))).to.eq(true);
+
+    // the received 990 tokens do not convert without a remainder
+    await setChainData(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      new anchor.BN(1000),
+      false,
+      mint.publicKey
+    );
+    await mintToUser(1000);
+    expect(
+      await revertsWithLog(
+        () =>
+          send(
+            TO_CHAIN_B32,
+            instance.toChainData,
+            user,
+            userTokenAccount_,
+            new anchor.BN(1),
+            { ...options, amount: new anchor.BN(1000) }
+          ),
+        ["BadToken"]
+      )
+    ).to.be.true;
+  });
+
+  it("should burn sent and mint fulfilled tokens in burn-and-mint mode", async () => {
+    const mint = await spl.createMint(
+      provider.connection,
+      owner,
+      owner.publicKey,
+      null,
+      6
+    );
+    const instance = await createInstance(mint);
+    const userTokenAccount_ = await spl.createAssociatedTokenAccount(
+      provider.connection,
+      user,
+      mint,
+      user.publicKey
+    );
+    await spl.mintTo(
+      provider.connection,
+      owner,
+      mint,
+      userTokenAccount_,
+      owner,
+      1000000
+    );
+
+    const setBurnAndMint = () =>
+      program.methods
+        .setBurnAndMint(mint, true, VERSION, CURRENT_CHAIN_B32)
+        .accounts({
+          owner: owner.publicKey,
+          tokenMint: mint,
+          bridgeTokenAccount: instance.bridgeTokenAccount,
+          bridgeParams: instance.bridgeParams,
+        })
+        .signers([owner])
+        .rpc();
+    expect(await revertsWithErr(setBurnAndMint, ["NotMintAuthority"])).to.be
+      .true;
+
+    await spl.setAuthority(
+      provider.connection,
+      owner,
+      mint,
+      owner,
+      spl.AuthorityType.MintTokens,
+      instance.bridgeTokenAccount
+    );
+    await setBurnAndMint();
+
+    await send(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(0),
+      { mint, amount: new anchor.BN(1000000) }
+    );
+
+    expect((await spl.getMint(provider.connection, mint)).supply).to.eq(
+      BigInt(0)
+    );
+    expect(
+      (
+        await spl.getAccount(
+          provider.connection,
+          instance.bridgeTokenAccount
+        )
+      ).amount
+    ).to.eq(BigInt(0));
+
+    await program.methods
+      .setMintCap(
+        mint,
+        new anchor.BN(500000),
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        chainData: instance.toChainData,
+      })
+      .signers([owner])
+      .rpc();
+
+    const fulfill_ = (nonce: anchor.BN, amount: anchor.BN) =>
+      fulfill(
+        TO_CHAIN_B32,
+        TO_CHAIN_BUFFER,
+        instance.toChainData,
+        user,
+        userTokenAccount_,
+        nonce,
+        NO_REMOTE_ADDRESS_B32,
+        NO_REMOTE_ADDRESS_B32,
+        { mint, amount }
+      );
+
+    await fulfill_(new anchor.BN(1), new anchor.BN(400000));
+    expect((await spl.getMint(provider.connection, mint)).supply).to.eq(
+      BigInt(400000)
+    );
+    expect(
+      (await spl.getAccount(provider.connection, userTokenAccount_)).amount
+    ).to.eq(BigInt(400000));
+    expect(
+      (await program.account.chainData.fetch(instance.toChainData)).minted.eq(
+        new anchor.BN(400000)
+      )
+    ).to.eq(true);
+
+    expect(
+      await revertsWithErr(
+        () => fulfill_(new anchor.BN(2), new anchor.BN(200000)),
+        ["MintCapExceeded"]
+      )
+    ).to.be.true;
+
+    // a refund mints the burned tokens again even if the cap was lowered since
+    await send(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(1),
+      { mint, amount: new anchor.BN(300000) }
+    );
+    const sendTx = getSendTxAccount(owner.publicKey, mint, new anchor.BN(1))[0];
+    const sendTxAccount = await program.account.bridgeSendTx.fetch(sendTx);
+    expect(sendTxAccount.burned).to.be.true;
+    expect(sendTxAccount.mintedBurned.eq(new anchor.BN(300000))).to.eq(true);
+    await program.methods
+      .setMintCap(
+        mint,
+        new anchor.BN(0),
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32
+      )
+      .accounts({
+        owner: owner.publicKey,
+        chainData: instance.toChainData,
+      })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .refund(new anchor.BN(1), false, VERSION, CURRENT_CHAIN_B32)
+      .accounts({
+        tokenMint: mint,
+        sendTx,
+        userTokenAccount: userTokenAccount_,
+        bridgeTokenAccount: instance.bridgeTokenAccount,
+        feeAccount: instance.feeAccount,
+        bridgeParams: instance.bridgeParams,
+        toChainData: instance.toChainData,
+        owner: owner.publicKey,
+      })
+      .signers([owner])
+      .rpc();
+    expect(
+      (await spl.getAccount(provider.connection, userTokenAccount_)).amount
+    ).to.eq(BigInt(400000));
+    expect(
+      (await program.account.chainData.fetch(instance.toChainData)).minted.eq(
+        new anchor.BN(400000)
+      )
+    ).to.eq(true);
+  });
+
+  it("should pass the extra accounts of a Token-2022 transfer hook", async () => {
+    const mint = new anchor.web3.Keypair();
+    // base account, account type and the transfer hook extension
+    const mintLen = 165 + 1 + 2 + 2 + 64;
+    // the installed @solana/spl-token has no transfer hook instructions yet
+    const initializeTransferHook = new anchor.web3.TransactionInstruction({
+      programId: spl.TOKEN_2022_PROGRAM_ID,
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      data: Buffer.concat([
+        Buffer.from([36, 0]),
+        provider.wallet.publicKey.toBuffer(),
+        hookProgram.programId.toBuffer(),
+      ]),
+    });
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          programId: spl.TOKEN_2022_PROGRAM_ID,
+          space: mintLen,
+          fromPubkey: provider.wallet.publicKey,
+          newAccountPubkey: mint.publicKey,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(
+            mintLen
+          ),
+        }),
+        initializeTransferHook,
+        spl.createInitializeMintInstruction(
+          mint.publicKey,
+          6,
+          provider.wallet.publicKey,
+          null,
+          spl.TOKEN_2022_PROGRAM_ID
+        )
+      ),
+      [mint]
+    );
+
+    const [counter] = PublicKey.findProgramAddressSync(
+      [Buffer.from("counter"), mint.publicKey.toBuffer()],
+      hookProgram.programId
+    );
+    const [extraAccountMetas] = PublicKey.findProgramAddressSync(
+      [Buffer.from("extra-account-metas"), mint.publicKey.toBuffer()],
+      hookProgram.programId
+    );
+    await hookProgram.methods
+      .initialize()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        counter,
+        extraAccountMetas,
+      })
+      .rpc();
+    const hookAccounts = [
+      { pubkey: counter, isSigner: false, isWritable: true },
+      { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: extraAccountMetas, isSigner: false, isWritable: false },
+    ];
+    const transfers = async () =>
+      (await hookProgram.account.transferCounter.fetch(
+        counter
+      )).transfers.toNumber();
+
+    const instance = await createInstance(
+      mint.publicKey,
+      spl.TOKEN_2022_PROGRAM_ID
+    );
+    const userTokenAccount_ = await spl.createAssociatedTokenAccount(
+      provider.connection,
+      user,
+      mint.publicKey,
+      user.publicKey,
+      {},
+      spl.TOKEN_2022_PROGRAM_ID
+    );
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        spl.createMintToInstruction(
+          mint.publicKey,
+          userTokenAccount_,
+          provider.wallet.publicKey,
+          1000000,
+          [],
+          spl.TOKEN_2022_PROGRAM_ID
+        )
+      )
+    );
+
+    const options = {
+      mint: mint.publicKey,
+      tokenProgram: spl.TOKEN_2022_PROGRAM_ID,
+      remainingAccounts: hookAccounts,
+    };
+    await send(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(0),
+      { ...options, amount: new anchor.BN(1000000) }
+    );
+    expect(await transfers()).to.eq(1);
+
+    await fulfill(
+      TO_CHAIN_B32,
+      TO_CHAIN_BUFFER,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(1),
+      NO_REMOTE_ADDRESS_B32,
+      NO_REMOTE_ADDRESS_B32,
+      { ...options, amount: new anchor.BN(400000) }
+    );
+    expect(await transfers()).to.eq(2);
+
+    // minted tokens are not owed to fulfills and can be withdrawn
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        spl.createMintToInstruction(
+          mint.publicKey,
+          instance.bridgeTokenAccount,
+          provider.wallet.publicKey,
+          1000,
+          [],
+          spl.TOKEN_2022_PROGRAM_ID
+        )
+      )
+    );
+    const withdrawDestination = getBridgeUserAccount(
+      "withdraw_destination",
+      owner.publicKey,
+      mint.publicKey,
+      instance.feeAccount
+    )[0];
+    await program.methods
+      .setWithdrawDestination(
+        mint.publicKey,
+        instance.feeAccount,
+        true,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({ owner: owner.publicKey, withdrawDestination })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .withdraw(new anchor.BN(1000), false, VERSION, CURRENT_CHAIN_B32)
+      .accounts({
+        tokenMint: mint.publicKey,
+        bridgeTokenAccount: instance.bridgeTokenAccount,
+        owner: owner.publicKey,
+        withdrawTokenAccount: instance.feeAccount,
+        withdrawDestination,
+        bridgeParams: instance.bridgeParams,
+        tokenProgram: spl.TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([owner])
+      .rpc();
+    expect(await transfers()).to.eq(3);
+    const feeBalance = async () =>
+      (
+        await spl.getAccount(
+          provider.connection,
+          instance.feeAccount,
+          undefined,
+          spl.TOKEN_2022_PROGRAM_ID
+        )
+      ).amount;
+    expect(await feeBalance()).to.eq(BigInt(1000));
+
+    // the fee is transferred separately, with the same extra accounts
+    await program.methods
+      .setParams(
+        mint.publicKey,
+        100,
+        100,
+        new anchor.BN(999999999),
+        new anchor.BN(999999999),
+        false,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        bridgeParams: instance.bridgeParams,
+        owner: owner.publicKey,
+        feeAccount: instance.feeAccount,
+      })
+      .signers([owner])
+      .rpc();
+    await send(
+      TO_CHAIN_B32,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(1),
+      { ...options, amount: new anchor.BN(100000) }
+    );
+    expect(await transfers()).to.eq(5);
+    expect(await feeBalance()).to.eq(BigInt(2000));
+
+    await fulfill(
+      TO_CHAIN_B32,
+      TO_CHAIN_BUFFER,
+      instance.toChainData,
+      user,
+      userTokenAccount_,
+      new anchor.BN(2),
+      NO_REMOTE_ADDRESS_B32,
+      NO_REMOTE_ADDRESS_B32,
+      { ...options, amount: new anchor.BN(100000) }
+    );
+    expect(await transfers()).to.eq(7);
+    expect(await feeBalance()).to.eq(BigInt(3000));
+  });
+
+  it("should create a wrapped mint for a remote token", async () => {
+    const remoteToken = ethAddress(TEST_ETH_ADDRESS);
+    const [wrappedMint] = PublicKey.findProgramAddressSync(
+      [
+        VERSION.toBuffer("be", 8),
+        Buffer.from("wrapped_mint"),
+        owner.publicKey.toBuffer(),
+        CURRENT_CHAIN_BUFFER,
+        TO_CHAIN_BUFFER,
+        Buffer.from(remoteToken.byte),
+      ],
+      program.programId
+    );
+    const bridgeTokenAccount_ = getBridgeAccount(
+      "wallet",
+      owner.publicKey,
+      wrappedMint
+    )[0];
+    const wrappedToken = getBridgeAccount(
+      "wrapped_token",
+      owner.publicKey,
+      wrappedMint
+    )[0];
+
+    await program.methods
+      .createWrappedMint(
+        9,
+        stringToBytes("Wrapped Token"),
+        stringToBytes("WTKN"),
+        VERSION,
+        CURRENT_CHAIN_B32,
+        TO_CHAIN_B32,
+        remoteToken
+      )
+      .accounts({
+        owner: owner.publicKey,
+        wrappedMint,
+        bridgeTokenAccount: bridgeTokenAccount_,
+        wrappedToken,
+      })
+      .signers([owner])
+      .rpc();
+
+    const mint = await spl.getMint(provider.connection, wrappedMint);
+    expect(mint.decimals).to.eq(9);
+    expect(mint.mintAuthority.toBase58()).to.eq(
+      bridgeTokenAccount_.toBase58()
+    );
+    const wrappedTokenAccount = await program.account.wrappedToken.fetch(
+      wrappedToken
+    );
+    expect(wrappedTokenAccount.remoteChain.byte).to.deep.eq(TO_CHAIN_B32.byte);
+    expect(wrappedTokenAccount.remoteToken.byte).to.deep.eq(remoteToken.byte);
+    expect(wrappedTokenAccount.symbol.byte).to.deep.eq(
+      stringToBytes("WTKN").byte
+    );
+
+    // the instance for the wrapped mint can use burn-and-mint mode right away
+    const ownerTokenAccount = await spl.createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      wrappedMint,
+      owner.publicKey
+    );
+    const bridgeParams_ = getBridgeAccount(
+      "bridge_params",
+      owner.publicKey,
+      wrappedMint
+    )[0];
+    await program.methods
+      .initialize(
+        0,
+        0,
+        new anchor.BN(999999999),
+        new anchor.BN(999999999),
+        false,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        tokenMint: wrappedMint,
+        bridgeTokenAccount: bridgeTokenAccount_,
+        owner: owner.publicKey,
+        feeAccount: ownerTokenAccount,
+        bridgeParams: bridgeParams_,
+      })
+      .signers([owner])
+      .rpc();
+    await program.methods
+      .setBurnAndMint(wrappedMint, true, VERSION, CURRENT_CHAIN_B32)
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: wrappedMint,
+        bridgeTokenAccount: bridgeTokenAccount_,
+        bridgeParams: bridgeParams_,
+      })
+      .signers([owner])
+      .rpc();
+    expect(
+      (await program.account.bridgeParams.fetch(bridgeParams_)).burnAndMint
+    ).to.be.true;
+  });
+
+  it("should send and fulfill native SOL", async () => {
+    const mint = spl.NATIVE_MINT;
+    const instance = await createInstance(mint, spl.TOKEN_PROGRAM_ID, {
+      limitSend: new anchor.BN(LAMPORTS_PER_SOL),
+      limitFulfill: new anchor.BN(LAMPORTS_PER_SOL),
+    });
+    const userTokenAccount_ = await spl.getAssociatedTokenAddress(
+      mint,
+      user.publicKey
+    );
+
+    const amount = LAMPORTS_PER_SOL / 10;
+    const sendNative = (globalNonce: anchor.BN) =>
+      program.methods
+        .sendNative(
+          owner.publicKey,
+          mint,
+          new anchor.BN(amount),
+          ethAddress(TEST_ETH_ADDRESS),
+          VERSION,
+          TO_CHAIN_B32,
+          CURRENT_CHAIN_B32
+        )
+        .accounts({
+          registry: getRegistryAccount(owner.publicKey)[0],
+          tokenMint: mint,
+          sendTx: getSendTxAccount(owner.publicKey, mint, globalNonce)[0],
+          sendNonce: getBridgeUserAccount(
+            "send_nonce",
+            owner.publicKey,
+            mint,
+            user.publicKey
+          )[0],
+          sendLimit: getBridgeUserAccount(
+            "send_limit",
+            owner.publicKey,
+            mint,
+            user.publicKey
+          )[0],
+          bridgeTokenAccount: instance.bridgeTokenAccount,
+          user: user.publicKey,
+          feeAccount: instance.feeAccount,
+          toChainData: instance.toChainData,
+          bridgeParams: instance.bridgeParams,
+        })
+        .signers([user])
+        .rpc();
+    const wrappedBalance = async (tokenAccount: PublicKey) =>
+      (await spl.getAccount(provider.connection, tokenAccount)).amount;
+    const sendTx = getSendTxAccount(owner.publicKey, mint, new anchor.BN(0))[0];
+    await sendNative(new anchor.BN(0));
+
+    // the lamports are sent without a wrapped SOL account of the user
+    expect(await provider.connection.getAccountInfo(userTokenAccount_)).to.be
+      .null;
+    expect(await wrappedBalance(instance.bridgeTokenAccount)).to.eq(
+      BigInt(amount)
+    );
+    expect(
+      (await program.account.bridgeSendTx.fetch(sendTx)).amount.eq(
+        new anchor.BN(amount)
+      )
+    ).to.eq(true);
+
+    const fulfillNative = (
+      nonce: anchor.BN,
+      amount: anchor.BN,
+      recipient: PublicKey
+    ) =>
+      program.methods
+        .fulfillNative(
+          nonce,
+          amount,
+          recipient,
+          VERSION,
+          CURRENT_CHAIN_B32,
+          TO_CHAIN_B32,
+          FROM_USER_B32,
+          inAnHour(),
+          SOURCE_TX_HASH_B32,
+          SOURCE_BLOCK,
+          NO_REMOTE_ADDRESS_B32,
+          NO_REMOTE_ADDRESS_B32
+        )
+        .accounts({
+          registry: getRegistryAccount(owner.publicKey)[0],
+          tokenMint: mint,
+          unwrapAccount: getBridgeAccount("unwrap", owner.publicKey, mint)[0],
+          bridgeTokenAccount: instance.bridgeTokenAccount,
+          user: recipient,
+          payer: owner.publicKey,
+          owner: owner.publicKey,
+          feeAccount: instance.feeAccount,
+          bridgeParams: instance.bridgeParams,
+          fulfilledBitmap: getFulfilledBitmapAccount(
+            owner.publicKey,
+            mint,
+            TO_CHAIN_BUFFER,
+            nonce
+          )[0],
+          legacyFulfilled: getLegacyFulfilledAccount(
+            owner.publicKey,
+            mint,
+            TO_CHAIN_BUFFER,
+            nonce
+          )[0],
+          fromChainData: instance.toChainData,
+        })
+        .signers([owner])
+        .rpc();
+
+    // a new recipient can not be created with less than the rent exemption
+    const recipient = new anchor.web3.Keypair();
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfillNative(
+            new anchor.BN(1),
+            new anchor.BN(1000),
+            recipient.publicKey
+          ),
+        ["AmountBelowRent"]
+      )
+    ).to.be.true;
+
+    await fulfillNative(
+      new anchor.BN(1),
+      new anchor.BN(amount),
+      recipient.publicKey
+    );
+    expect(await provider.connection.getBalance(recipient.publicKey)).to.eq(
+      amount
+    );
+    expect(await wrappedBalance(instance.bridgeTokenAccount)).to.eq(
+      BigInt(0)
+    );
+
+    // the fee is wrapped into the fee account as well
+    await program.methods
+      .setParams(
+        mint,
+        100,
+        0,
+        new anchor.BN(LAMPORTS_PER_SOL),
+        new anchor.BN(LAMPORTS_PER_SOL),
+        false,
+        VERSION,
+        CURRENT_CHAIN_B32
+      )
+      .accounts({
+        bridgeParams: instance.bridgeParams,
+        owner: owner.publicKey,
+        feeAccount: instance.feeAccount,
+      })
+      .signers([owner])
+      .rpc();
+    await sendNative(new anchor.BN(1));
+    expect(await wrappedBalance(instance.bridgeTokenAccount)).to.eq(
+      BigInt(amount - amount / 100)
+    );
+    expect(await wrappedBalance(instance.feeAccount)).to.eq(
+      BigInt(amount / 100)
+    );
+  });
+
+  it("should pause all instances of a registry together", async () => {
+    const registry = getRegistryAccount(owner.publicKey)[0];
+    await program.methods
+      .initializeRegistry(VERSION, CURRENT_CHAIN_B32)
+      .accounts({ owner: owner.publicKey, registry })
+      .signers([owner])
+      .rpc();
+
+    // the instance of native SOL created before is registered as well
+    const nativeMint = spl.NATIVE_MINT;
+    const nativeBridgeParams = getBridgeAccount(
+      "bridge_params",
+      owner.publicKey,
+      nativeMint
+    )[0];
+    const registerMint = (mint: PublicKey, bridgeParams_: PublicKey) =>
+      program.methods
+        .registerMint(mint, VERSION, CURRENT_CHAIN_B32)
+        .accounts({
+          owner: owner.publicKey,
+          registry,
+          bridgeParams: bridgeParams_,
+        })
+        .signers([owner])
+        .rpc();
+    await registerMint(tokenMint, bridgeParams);
+    await registerMint(nativeMint, nativeBridgeParams);
+    expect(
+      await revertsWithErr(
+        () => registerMint(tokenMint, bridgeParams),
+        ["AlreadyRegistered"]
+      )
+    ).to.be.true;
+    const registryAccount = await program.account.registry.fetch(registry);
+    expect(registryAccount.mints.length).to.eq(2);
+    expect(registryAccount.mints[0].toBase58()).to.eq(tokenMint.toBase58());
+    expect(registryAccount.mints[1].toBase58()).to.eq(nativeMint.toBase58());
+
+    const setRegistryPaused = (paused: boolean) =>
+      program.methods
+        .setRegistryPaused(paused, VERSION, CURRENT_CHAIN_B32)
+        .accounts({ owner: owner.publicKey, registry })
+        .signers([owner])
+        .rpc();
+    const setPaused = async (paused: boolean) => {
+      const params = await program.account.bridgeParams.fetch(bridgeParams);
+      await program.methods
+        .setParams(
+          tokenMint,
+          params.feeSend,
+          params.feeFulfill,
+          params.limitSend,
+          params.limitFulfill,
+          paused,
+          VERSION,
+          CURRENT_CHAIN_B32
+        )
+        .accounts({
+          bridgeParams,
+          owner: owner.publicKey,
+          feeAccount: params.feeRecipient,
+        })
+        .signers([owner])
+        .rpc();
+    };
+    const sendToken = async () =>
+      send(
+        TO_CHAIN_B32,
+        toChainData,
+        user,
+        userTokenAccount,
+        await getGlobalSendNonce()
+      );
+    const sendNative = async () => {
+      const params = await program.account.bridgeParams.fetch(
+        nativeBridgeParams
+      );
+      return program.methods
+        .sendNative(
+          owner.publicKey,
+          nativeMint,
+          new anchor.BN(LAMPORTS_PER_SOL / 100),
+          ethAddress(TEST_ETH_ADDRESS),
+          VERSION,
+          TO_CHAIN_B32,
+          CURRENT_CHAIN_B32
+        )
+        .accounts({
+          registry,
+          tokenMint: nativeMint,
+          sendTx: getSendTxAccount(
+            owner.publicKey,
+            nativeMint,
+            params.sendNonce
+          )[0],
+          sendNonce: getBridgeUserAccount(
+            "send_nonce",
+            owner.publicKey,
+            nativeMint,
+            user.publicKey
+          )[0],
+          sendLimit: getBridgeUserAccount(
+            "send_limit",
+            owner.publicKey,
+            nativeMint,
+            user.publicKey
+          )[0],
+          bridgeTokenAccount: getBridgeAccount(
+            "wallet",
+            owner.publicKey,
+            nativeMint
+          )[0],
+          user: user.publicKey,
+          feeAccount: params.feeRecipient,
+          toChainData: getChainDataAccount(
+            owner.publicKey,
+            nativeMint,
+            TO_CHAIN_BUFFER
+          )[0],
+          bridgeParams: nativeBridgeParams,
+        })
+        .signers([user])
+        .rpc();
+    };
+
+    // the registry pauses all its instances without touching their own pause state
+    await setRegistryPaused(true);
+    expect((await program.account.bridgeParams.fetch(bridgeParams)).paused).to
+      .be.false;
+    expect(await revertsWithErr(sendToken, ["BridgePaused"])).to.be.true;
+    expect(await revertsWithErr(sendNative, ["BridgePaused"])).to.be.true;
+
+    // unpausing an instance does not lift the pause of the registry
+    await setPaused(false);
+    expect(await revertsWithErr(sendToken, ["BridgePaused"])).to.be.true;
+
+    // unpausing the registry does not unpause an instance paused on its own
+    await setPaused(true);
+    await setRegistryPaused(false);
+    expect(await revertsWithErr(sendToken, ["BridgePaused"])).to.be.true;
+    await sendNative();
+
+    await setPaused(false);
+    await sendToken();
+
+    // an unregistered instance is no longer paused by the registry
+    const unregisterMint = (mint: PublicKey) =>
+      program.methods
+        .unregisterMint(mint, VERSION, CURRENT_CHAIN_B32)
+        .accounts({ owner: owner.publicKey, registry })
+        .signers([owner])
+        .rpc();
+    await setRegistryPaused(true);
+    await unregisterMint(nativeMint);
+    expect(
+      (await program.account.registry.fetch(registry)).mints.length
+    ).to.eq(1);
+    expect(
+      await revertsWithErr(() => unregisterMint(nativeMint), ["NotRegistered"])
+    ).to.be.true;
+    await sendNative();
+    expect(await revertsWithErr(sendToken, ["BridgePaused"])).to.be.true;
+    await setRegistryPaused(false);
+  });
+
+  it("should record the route in send txs and check it on fulfill", async () => {
+    const remoteToken = ethAddress(
+      "0x5FbDB2315678afecb367f032d93F642f64180aa3"
+    );
+    const remoteBridge = ethAddress(
+      "0xe7f1725E7734CE288F8367e1Bb143E90bb3F0512"
+    );
+    const setRemoteRoute = (token, bridge) =>
+      program.methods
+        .setRemoteRoute(
+          tokenMint,
+          token,
+          bridge,
+          VERSION,
+          CURRENT_CHAIN_B32,
+          TO_CHAIN_B32
+        )
+        .accounts({ owner: owner.publicKey, chainData: toChainData })
+        .signers([owner])
+        .rpc();
+    await setRemoteRoute(remoteToken, remoteBridge);
+
+    const { sendTx } = await send(
+      TO_CHAIN_B32,
+      toChainData,
+      user,
+      userTokenAccount,
+      await getGlobalSendNonce()
+    );
+    const sendTxAccount = await program.account.bridgeSendTx.fetch(sendTx);
+    expect(sendTxAccount.remoteToken.byte).to.deep.eq(remoteToken.byte);
+    expect(sendTxAccount.remoteBridge.byte).to.deep.eq(remoteBridge.byte);
+
+    const nonce = new anchor.BN(13);
+    expect(
+      await revertsWithErr(
+        () =>
+          fulfill(
+            TO_CHAIN_B32,
+            TO_CHAIN_BUFFER,
+            toChainData,
+            user,
+            userTokenAccount,
+            nonce,
+            remoteToken,
+            FROM_USER_B32
+          ),
+        ["RouteMismatch"]
+      )
+    ).to.be.true;
+    await fulfill(
+      TO_CHAIN_B32,
+      TO_CHAIN_BUFFER,
+      toChainData,
+      user,
+      userTokenAccount,
+      nonce,
+      remoteToken,
+      remoteBridge
+    );
+
+    await setRemoteRoute(NO_REMOTE_ADDRESS_B32, NO_REMOTE_ADDRESS_B32);
+  });
+
+  it("should enforce the withdraw limit", async () => {
+    const setWithdrawLimit = (limit: anchor.BN) =>
+      program.methods
+        .setWithdrawLimit(
+          tokenMint,
+          limit,
+          new anchor.BN(86400),
+          VERSION,
+          CURRENT_CHAIN_B32
+        )
+        .accounts({ owner: owner.publicKey, bridgeParams })
+        .signers([owner])
+        .rpc();
+    await setWithdrawLimit(new anchor.BN(100));
+
+    // the tokens left in the bridge are owed to fulfills
+    await requestWithdrawOverride();
+    await sleep(3000);
+    await withdraw(new anchor.BN(60), owner, true);
+    expect(
+      await revertsWithErr(
+        () => withdraw(new anchor.BN(60), owner, true),
+        ["WithdrawLimitExceeded"]
+      )
+    ).to.be.true;
+    await requestWithdrawOverride();
+    await sleep(3000);
+    await withdraw(new anchor.BN(40), owner, true);
+
+    await setWithdrawLimit(new anchor.BN(0));
+  });
+
+  it("should not migrate accounts in the current layout", async () => {
+    expect(
+      await revertsWithErr(
+        () =>
+          program.methods
+            .migrateParams(tokenMint, VERSION, CURRENT_CHAIN_B32)
+            .accounts({
+              owner: owner.publicKey,
+              bridgeParams,
+              bridgeTokenAccount,
+            })
+            .signers([owner])
+            .rpc(),
+        ["AlreadyMigrated"]
+      )
+    ).to.be.true;
+    expect(
+      await revertsWithErr(
+        () =>
+          program.methods
+            .migrateChainData(
+              tokenMint,
+              VERSION,
+              CURRENT_CHAIN_B32,
+              TO_CHAIN_B32
+            )
+            .accounts({ owner: owner.publicKey, chainData: toChainData })
+            .signers([owner])
+            .rpc(),
+        ["AlreadyMigrated"]
+      )
+    ).to.be.true;
+  });
 });